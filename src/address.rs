@@ -0,0 +1,88 @@
+use std::fmt::{Debug, Display, Formatter};
+use std::str::FromStr;
+use anyhow::format_err;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use web3::types::H160;
+
+/// An account address, parsed from 20 bytes of hex with or without a `0x` prefix and in any case.
+/// It is always displayed in its canonical form, `0x` followed by 40 lowercase hex digits,
+/// which is the form stored in the database and used in merkle leaves.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct AccountAddress(pub H160);
+
+impl AccountAddress {
+    pub fn as_h160(&self) -> &H160 {
+        &self.0
+    }
+}
+
+impl From<H160> for AccountAddress {
+    fn from(address: H160) -> Self {
+        Self(address)
+    }
+}
+
+impl FromStr for AccountAddress {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let hex = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")).unwrap_or(s);
+        if hex.len() != 40 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format_err!("invalid address: {}", s));
+        }
+        let address = H160::from_str(hex).map_err(|e| format_err!("invalid address {},{:?}", s, e))?;
+        Ok(Self(address))
+    }
+}
+
+impl Display for AccountAddress {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        // the Display of H160 elides the middle digits, its Debug prints all of them
+        write!(f, "{:?}", self.0)
+    }
+}
+
+impl Debug for AccountAddress {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+impl Serialize for AccountAddress {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for AccountAddress {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        AccountAddress::from_str(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_normalizes_address() {
+        let canonical = "0x558038f070a802182355a0fa4807575f30076ced";
+        for input in [
+            "0x558038F070A802182355A0FA4807575f30076CeD",
+            "558038f070a802182355a0fa4807575f30076ced",
+            " 0X558038F070A802182355A0FA4807575F30076CED ",
+        ] {
+            assert_eq!(AccountAddress::from_str(input).unwrap().to_string(), canonical);
+        }
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed_address() {
+        for input in ["", "0", "0x", "0x558038f070a802182355a0fa4807575f30076ce",
+            "0x558038f070a802182355a0fa4807575f30076cedd", "0x558038f070a802182355a0fa4807575f30076cez"] {
+            assert!(AccountAddress::from_str(input).is_err(), "{input} should be rejected");
+        }
+    }
+}
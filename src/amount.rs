@@ -0,0 +1,154 @@
+use std::str::FromStr;
+use anyhow::format_err;
+use bigdecimal::{BigDecimal, One, Signed, Zero};
+use num::BigInt;
+use num::bigint::ToBigInt;
+use num::ToPrimitive;
+use serde::{Deserialize, Serialize};
+
+/// How an amount with more fractional digits than the token decimals is rounded to base units.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rounding {
+    /// Towards zero, never allocating more than computed.
+    Down,
+    /// Away from zero.
+    Up,
+    /// To the nearest base unit, halves away from zero.
+    HalfUp,
+}
+
+impl FromStr for Rounding {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "down" => Ok(Rounding::Down),
+            "up" => Ok(Rounding::Up),
+            "half_up" => Ok(Rounding::HalfUp),
+            other => Err(format_err!("unknown rounding mode: {}", other)),
+        }
+    }
+}
+
+/// A token amount, both in whole tokens and in the token's base units.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TokenAmount {
+    pub human: BigDecimal,
+    pub base_units: BigInt,
+}
+
+impl TokenAmount {
+    /// Scales an amount of whole tokens to base units. `human` is the rounded amount.
+    pub fn from_human(amount: &BigDecimal, decimals: u32, rounding: Rounding) -> Self {
+        let scaled = amount * pow10(decimals);
+        let truncated = scaled.with_scale(0);
+        let remainder = (&scaled - &truncated).abs();
+        let round_away = match rounding {
+            Rounding::Down => false,
+            Rounding::Up => !remainder.is_zero(),
+            Rounding::HalfUp => remainder >= BigDecimal::from_str("0.5").unwrap(),
+        };
+        let mut base_units = truncated.to_bigint().expect("a decimal with scale 0 is an integer");
+        if round_away {
+            if scaled.is_negative() {
+                base_units -= BigInt::one();
+            } else {
+                base_units += BigInt::one();
+            }
+        }
+        Self::from_base_units(base_units, decimals)
+    }
+
+    pub fn from_base_units(base_units: BigInt, decimals: u32) -> Self {
+        let human = BigDecimal::new(base_units.clone(), decimals as i64).normalized();
+        Self { human, base_units }
+    }
+
+    pub fn zero() -> Self {
+        Self { human: BigDecimal::zero(), base_units: BigInt::zero() }
+    }
+}
+
+/// Scales `amounts` down pro-rata so they sum to `budget` when their sum exceeds it.
+///
+/// Every amount is first rounded down, then the remaining dust is handed out one base unit at
+/// a time to the largest remainders, ties going to the lowest index, so the result only
+/// depends on the order of `amounts`.
+pub fn scale_to_budget(amounts: &[BigInt], budget: &BigInt) -> Vec<BigInt> {
+    let total: BigInt = amounts.iter().sum();
+    if &total <= budget {
+        return amounts.to_vec();
+    }
+    let mut scaled = Vec::with_capacity(amounts.len());
+    let mut remainders = Vec::with_capacity(amounts.len());
+    for (i, amount) in amounts.iter().enumerate() {
+        let product = amount * budget;
+        scaled.push(&product / &total);
+        remainders.push((product % &total, i));
+    }
+    let distributed: BigInt = scaled.iter().sum();
+    let dust = (budget - distributed).to_usize().expect("dust is less than the number of amounts");
+    remainders.sort_by(|(ra, ia), (rb, ib)| rb.cmp(ra).then(ia.cmp(ib)));
+    for (_, i) in remainders.into_iter().take(dust) {
+        scaled[i] += BigInt::one();
+    }
+    scaled
+}
+
+fn pow10(decimals: u32) -> BigDecimal {
+    BigDecimal::new(BigInt::one(), -(decimals as i64))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn amount(s: &str) -> BigDecimal {
+        BigDecimal::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_scale_to_base_units() {
+        let a = TokenAmount::from_human(&amount("12.5"), 18, Rounding::Down);
+        assert_eq!(a.base_units.to_string(), "12500000000000000000");
+        assert_eq!(a.human, amount("12.5"));
+        let a = TokenAmount::from_human(&amount("7"), 0, Rounding::Down);
+        assert_eq!(a.base_units.to_string(), "7");
+        let a = TokenAmount::from_base_units(BigInt::from(1000), 0);
+        assert_eq!(a.human.to_string(), "1000");
+    }
+
+    #[test]
+    fn test_scale_to_budget() {
+        let amounts = [100, 200, 300, 0, 1].map(BigInt::from);
+        assert_eq!(scale_to_budget(&amounts, &BigInt::from(1000)), amounts.to_vec());
+
+        let amounts = [1, 1, 1].map(BigInt::from);
+        assert_eq!(scale_to_budget(&amounts, &BigInt::from(2)), [1, 1, 0].map(BigInt::from).to_vec());
+
+        let amounts = [50, 30, 20, 7].map(BigInt::from);
+        let scaled = scale_to_budget(&amounts, &BigInt::from(10));
+        assert_eq!(scaled, [5, 3, 2, 0].map(BigInt::from).to_vec());
+
+        let amounts = [333, 333, 334, 1000].map(BigInt::from);
+        let scaled = scale_to_budget(&amounts, &BigInt::from(1001));
+        assert_eq!(scaled.iter().sum::<BigInt>(), BigInt::from(1001));
+        assert_eq!(scaled, [167, 167, 167, 500].map(BigInt::from).to_vec());
+    }
+
+    #[test]
+    fn test_rounding_modes() {
+        let cases = [
+            ("1.2345", Rounding::Down, "123"),
+            ("1.2345", Rounding::Up, "124"),
+            ("1.2345", Rounding::HalfUp, "123"),
+            ("1.2350", Rounding::HalfUp, "124"),
+            ("1.2300", Rounding::Up, "123"),
+        ];
+        for (human, rounding, base_units) in cases {
+            let a = TokenAmount::from_human(&amount(human), 2, rounding);
+            assert_eq!(a.base_units.to_string(), base_units, "{human} {rounding:?}");
+        }
+    }
+}
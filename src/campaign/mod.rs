@@ -0,0 +1,311 @@
+use std::fmt::{Debug, Formatter};
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use anyhow::format_err;
+use rbatis::RBatis;
+use serde::{Deserialize, Serialize};
+use crate::config::Config;
+use crate::db;
+use crate::reward::RewardEngine;
+use crate::tree;
+
+/// The phases a campaign goes through, in order. A campaign only ever moves forward.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CampaignPhase {
+    /// Accounts query their eligibility, which records their claimable amount.
+    Registration,
+    /// No more queries are accepted, the eligible tree is built and its root can be deployed.
+    SnapshotFrozen,
+    /// Proofs are served and claims are indexed from chain.
+    Claiming,
+    /// The campaign is over, remaining claims are still indexed.
+    Ended,
+}
+
+impl CampaignPhase {
+    pub fn next(self) -> Option<CampaignPhase> {
+        match self {
+            CampaignPhase::Registration => Some(CampaignPhase::SnapshotFrozen),
+            CampaignPhase::SnapshotFrozen => Some(CampaignPhase::Claiming),
+            CampaignPhase::Claiming => Some(CampaignPhase::Ended),
+            CampaignPhase::Ended => None,
+        }
+    }
+
+    pub fn can_transition_to(self, to: CampaignPhase) -> bool {
+        self.next() == Some(to)
+    }
+
+    /// Whether the eligible tree is frozen and can be served.
+    pub fn is_tree_frozen(self) -> bool {
+        self >= CampaignPhase::SnapshotFrozen
+    }
+
+    /// Whether claims may happen on chain and have to be indexed.
+    pub fn is_claim_started(self) -> bool {
+        self >= CampaignPhase::Claiming
+    }
+}
+
+impl Debug for CampaignPhase {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_ref())
+    }
+}
+
+impl AsRef<str> for CampaignPhase {
+    fn as_ref(&self) -> &'static str {
+        match self {
+            CampaignPhase::Registration => "registration",
+            CampaignPhase::SnapshotFrozen => "snapshot_frozen",
+            CampaignPhase::Claiming => "claiming",
+            CampaignPhase::Ended => "ended",
+        }
+    }
+}
+
+impl FromStr for CampaignPhase {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "registration" => Ok(CampaignPhase::Registration),
+            "snapshot_frozen" => Ok(CampaignPhase::SnapshotFrozen),
+            "claiming" => Ok(CampaignPhase::Claiming),
+            "ended" => Ok(CampaignPhase::Ended),
+            other => Err(format_err!("unknown campaign phase: {}", other)),
+        }
+    }
+}
+
+/// When a phase is entered automatically, written as `time:<unix seconds>` or `block:<number>`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PhaseTrigger {
+    Time(u64),
+    Block(u64),
+}
+
+impl PhaseTrigger {
+    pub fn is_reached(&self, now: u64, block_number: Option<u64>) -> bool {
+        match self {
+            PhaseTrigger::Time(time) => now >= *time,
+            PhaseTrigger::Block(block) => block_number.map(|b| b >= *block).unwrap_or(false),
+        }
+    }
+}
+
+impl FromStr for PhaseTrigger {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, value) = s.split_once(':')
+            .ok_or_else(|| format_err!("invalid phase trigger: {}", s))?;
+        let value = value.trim().parse::<u64>()
+            .map_err(|e| format_err!("invalid phase trigger {},{:?}", s, e))?;
+        match kind.trim() {
+            "time" => Ok(PhaseTrigger::Time(value)),
+            "block" => Ok(PhaseTrigger::Block(value)),
+            other => Err(format_err!("unknown phase trigger kind: {}", other)),
+        }
+    }
+}
+
+/// The current campaign phase, shared by the http server and the watcher and persisted in postgres.
+#[derive(Clone)]
+pub struct CampaignState {
+    db: RBatis,
+    phase: Arc<RwLock<CampaignPhase>>,
+    schedule: Vec<(CampaignPhase, PhaseTrigger)>,
+    reward_engine: Arc<RewardEngine>,
+}
+
+impl CampaignState {
+    /// Loads the persisted phase, then moves forward to the phase requested by `CAMPAIGN_PHASE` if any.
+    pub async fn load(config: &Config, db: RBatis, reward_engine: Arc<RewardEngine>) -> anyhow::Result<Self> {
+        let mut schedule = vec![];
+        for (phase, trigger) in [
+            (CampaignPhase::SnapshotFrozen, &config.phase_snapshot_frozen_at),
+            (CampaignPhase::Claiming, &config.phase_claiming_at),
+            (CampaignPhase::Ended, &config.phase_ended_at),
+        ] {
+            if !trigger.is_empty() {
+                schedule.push((phase, PhaseTrigger::from_str(trigger)?));
+            }
+        }
+        db::init_campaign_phase(&db, CampaignPhase::Registration.as_ref(), now()).await?;
+        let phase = db::get_campaign_phase(&db).await?
+            .ok_or_else(|| format_err!("campaign phase is not initialized"))?;
+        let phase = CampaignPhase::from_str(&phase)?;
+        let state = Self {
+            db,
+            phase: Arc::new(RwLock::new(phase)),
+            schedule,
+            reward_engine,
+        };
+        if !config.campaign_phase.is_empty() {
+            let requested = CampaignPhase::from_str(&config.campaign_phase)?;
+            if requested < phase {
+                return Err(format_err!("campaign is already in phase {:?}, can't go back to {:?}", phase, requested));
+            }
+            state.advance_to(requested).await?;
+        }
+        log::info!("campaign phase is {:?}", state.current());
+        Ok(state)
+    }
+
+    pub fn current(&self) -> CampaignPhase {
+        *self.phase.read().unwrap()
+    }
+
+    /// Moves to `to`, which must directly follow the current phase. Returns false if another
+    /// process changed the phase first, in which case the phase it set is reloaded.
+    pub async fn transition(&self, to: CampaignPhase) -> anyhow::Result<bool> {
+        let from = self.current();
+        if !from.can_transition_to(to) {
+            return Err(format_err!("invalid campaign phase transition from {:?} to {:?}", from, to));
+        }
+        let updated = if to == CampaignPhase::SnapshotFrozen {
+            self.freeze(from).await?
+        } else {
+            db::update_campaign_phase(&self.db, from.as_ref(), to.as_ref(), now()).await?
+        };
+        if !updated {
+            self.reload().await?;
+            log::warn!("campaign phase changed concurrently to {:?}", self.current());
+            return Ok(false);
+        }
+        *self.phase.write().unwrap() = to;
+        log::info!("campaign phase changed from {:?} to {:?}", from, to);
+        Ok(true)
+    }
+
+    /// Saves the snapshot of the queried accounts and moves from `from` to `SnapshotFrozen` in one
+    /// transaction, so the campaign is never frozen without a snapshot. Returns false, saving nothing,
+    /// if the phase wasn't `from` anymore.
+    async fn freeze(&self, from: CampaignPhase) -> anyhow::Result<bool> {
+        let tree_values = tree::queried_tree_values(&self.db, &self.reward_engine).await?;
+        let mut tx = self.db.acquire_begin().await?;
+        let ret = async {
+            let tree = tree::save_snapshot(&tx, tree_values).await?;
+            let updated = db::update_campaign_phase(&tx, from.as_ref(), CampaignPhase::SnapshotFrozen.as_ref(), now()).await?;
+            if updated {
+                db::set_campaign_snapshot_version(&tx, tree.version()).await?;
+            }
+            Ok(updated)
+        }.await;
+        if let Ok(false) = ret {
+            tx.rollback().await?;
+            return Ok(false);
+        }
+        db::finish_transaction(tx, ret).await
+    }
+
+    /// Moves forward through every phase up to `to`.
+    pub async fn advance_to(&self, to: CampaignPhase) -> anyhow::Result<()> {
+        while self.current() < to {
+            let next = self.current().next().expect("a phase before another one has a next phase");
+            self.transition(next).await?;
+        }
+        Ok(())
+    }
+
+    /// Applies every scheduled transition whose trigger is reached.
+    pub async fn apply_schedule(&self, block_number: Option<u64>) -> anyhow::Result<()> {
+        let now = now() as u64;
+        while let Some(next) = self.current().next() {
+            let reached = self.schedule.iter()
+                .any(|(phase, trigger)| *phase == next && trigger.is_reached(now, block_number));
+            if !reached {
+                break;
+            }
+            self.transition(next).await?;
+        }
+        Ok(())
+    }
+
+    /// Reads the phase persisted by another process.
+    pub async fn reload(&self) -> anyhow::Result<()> {
+        if let Some(phase) = db::get_campaign_phase(&self.db).await? {
+            let phase = CampaignPhase::from_str(&phase)?;
+            let mut current = self.phase.write().unwrap();
+            if *current != phase {
+                log::info!("campaign phase reloaded as {:?}", phase);
+                *current = phase;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reloads the phase every `period`, for processes which don't apply the schedule themselves.
+    pub async fn run_refresher(self, period: Duration) {
+        let mut refresh = tokio::time::interval(period);
+        loop {
+            refresh.tick().await;
+            if let Err(e) = self.reload().await {
+                log::error!("reload campaign phase error occurred {:?}", e);
+            }
+        }
+    }
+}
+
+fn now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).expect("Time went backwards").as_secs() as i64
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const PHASES: [CampaignPhase; 4] = [
+        CampaignPhase::Registration,
+        CampaignPhase::SnapshotFrozen,
+        CampaignPhase::Claiming,
+        CampaignPhase::Ended,
+    ];
+
+    #[test]
+    fn test_phase_order() {
+        for (i, phase) in PHASES.iter().enumerate() {
+            assert_eq!(phase.next(), PHASES.get(i + 1).copied());
+            for (j, to) in PHASES.iter().enumerate() {
+                assert_eq!(phase.can_transition_to(*to), j == i + 1, "{:?} to {:?}", phase, to);
+                assert_eq!(phase < to, i < j);
+            }
+        }
+        assert!(!CampaignPhase::Registration.is_tree_frozen());
+        assert!(CampaignPhase::SnapshotFrozen.is_tree_frozen());
+        assert!(!CampaignPhase::SnapshotFrozen.is_claim_started());
+        assert!(CampaignPhase::Claiming.is_claim_started());
+        assert!(CampaignPhase::Ended.is_claim_started());
+    }
+
+    #[test]
+    fn test_phase_names() {
+        for phase in PHASES {
+            assert_eq!(CampaignPhase::from_str(phase.as_ref()).unwrap(), phase);
+        }
+        assert_eq!(CampaignPhase::from_str(" Claiming ").unwrap(), CampaignPhase::Claiming);
+        assert!(CampaignPhase::from_str("frozen").is_err());
+    }
+
+    #[test]
+    fn test_trigger_parsing() {
+        assert_eq!(PhaseTrigger::from_str("time:1700000000").unwrap(), PhaseTrigger::Time(1700000000));
+        assert_eq!(PhaseTrigger::from_str(" block : 42").unwrap(), PhaseTrigger::Block(42));
+        assert!(PhaseTrigger::from_str("1700000000").is_err());
+        assert!(PhaseTrigger::from_str("time:soon").is_err());
+        assert!(PhaseTrigger::from_str("time:-1").is_err());
+        assert!(PhaseTrigger::from_str("epoch:1").is_err());
+    }
+
+    #[test]
+    fn test_trigger_reached() {
+        assert!(!PhaseTrigger::Time(100).is_reached(99, Some(1000)));
+        assert!(PhaseTrigger::Time(100).is_reached(100, None));
+        assert!(!PhaseTrigger::Block(100).is_reached(1000, None));
+        assert!(!PhaseTrigger::Block(100).is_reached(1000, Some(99)));
+        assert!(PhaseTrigger::Block(100).is_reached(0, Some(100)));
+    }
+}
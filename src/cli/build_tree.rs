@@ -0,0 +1,61 @@
+use std::fs;
+use std::path::PathBuf;
+use clap::Args;
+use crate::config::Config;
+use crate::eligibility::snapshot::SnapshotProvider;
+use crate::cli::open_db;
+use crate::reward::RewardEngine;
+use crate::tree;
+use crate::tree::TreeDump;
+
+#[derive(Args)]
+pub struct BuildTreeArgs {
+    /// Build from a CSV or JSON eligibility snapshot instead of the queried accounts.
+    #[arg(long)]
+    pub snapshot: Option<String>,
+    /// Where to write the OpenZeppelin compatible tree dump.
+    #[arg(long, default_value = "tree.json")]
+    pub out: PathBuf,
+    /// Where to write the tree root.
+    #[arg(long, default_value = "root.txt")]
+    pub root_out: PathBuf,
+    /// Total budget in whole tokens, overrides TOTAL_BUDGET.
+    #[arg(long)]
+    pub budget: Option<String>,
+    /// Also save the tree as a new merkle snapshot version, which servers serve once pinned by MERKLE_SNAPSHOT_VERSION.
+    #[arg(long)]
+    pub freeze: bool,
+}
+
+pub async fn run(config: Config, args: BuildTreeArgs) -> anyhow::Result<()> {
+    let mut engine = RewardEngine::from_config(&config)?;
+    if let Some(budget) = &args.budget {
+        engine.set_total_budget(budget)?;
+    }
+    let needs_db = args.snapshot.is_none() || args.freeze;
+    let rb = if needs_db { Some(open_db(&config).await?) } else { None };
+    let allocations = match (&args.snapshot, &rb) {
+        (Some(path), _) => SnapshotProvider::load(path)?.allocations(&engine),
+        (None, Some(rb)) => tree::queried_allocations(rb, &engine).await?,
+        (None, None) => unreachable!("the database is initialized without a snapshot"),
+    };
+    let tree_values = tree::tree_values(allocations, &engine);
+    let (tree, version) = match &rb {
+        Some(rb) if args.freeze => {
+            let eligible_tree = tree::save_snapshot(rb, tree_values.clone()).await?;
+            (eligible_tree.tree().clone(), Some(eligible_tree.version()))
+        },
+        _ => (tree::build_tree(tree_values.clone()), None),
+    };
+
+    fs::write(&args.out, serde_json::to_string_pretty(&TreeDump::of(&tree)?)?)?;
+    fs::write(&args.root_out, format!("{}\n", tree.root()))?;
+    println!("root: {}", tree.root());
+    println!("leaves: {}", tree_values.len());
+    println!("total amount: {}", tree::total_amount(&tree_values));
+    if let Some(version) = version {
+        println!("snapshot version: {}", version);
+    }
+    println!("tree written to {}, root written to {}", args.out.display(), args.root_out.display());
+    Ok(())
+}
@@ -0,0 +1,85 @@
+use std::fs;
+use std::path::PathBuf;
+use clap::{Args, ValueEnum};
+use crate::config::Config;
+use crate::db::tables::{ClaimedAccount, ClaimEventRecord, QueryAccount};
+use crate::cli::open_db;
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ExportTable {
+    QueryAccounts,
+    ClaimedAccounts,
+    ClaimEvents,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+#[derive(Args)]
+pub struct ExportArgs {
+    #[arg(long, value_enum)]
+    pub table: ExportTable,
+    #[arg(long, value_enum, default_value = "csv")]
+    pub format: ExportFormat,
+    #[arg(long)]
+    pub out: PathBuf,
+}
+
+pub async fn run(config: Config, args: ExportArgs) -> anyhow::Result<()> {
+    let rb = open_db(&config).await?;
+    let (header, rows) = match args.table {
+        ExportTable::QueryAccounts => {
+            let accounts = QueryAccount::select_all(&rb).await?;
+            let rows = accounts.iter().map(|a| vec![
+                a.address.clone(),
+                a.claimable_amount.0.to_string(),
+                a.query_time.to_string(),
+                a.eth_gas_cost.as_ref().map(|g| g.0.to_string()).unwrap_or_default(),
+            ]).collect::<Vec<_>>();
+            (vec!["address", "claimable_amount", "query_time", "eth_gas_cost"], rows)
+        },
+        ExportTable::ClaimedAccounts => {
+            let accounts = ClaimedAccount::select_all(&rb).await?;
+            let rows = accounts.iter().map(|a| vec![
+                a.address.clone(),
+                a.claimed_amount.0.to_string(),
+                a.claimed_time.to_string(),
+            ]).collect::<Vec<_>>();
+            (vec!["address", "claimed_amount", "claimed_time"], rows)
+        },
+        ExportTable::ClaimEvents => {
+            let events = ClaimEventRecord::select_all(&rb).await?;
+            let rows = events.iter().map(|e| vec![
+                e.tx_hash.clone(),
+                e.log_index.to_string(),
+                e.block_number.to_string(),
+                e.block_hash.clone(),
+                e.address.clone(),
+                e.amount.0.to_string(),
+                e.claimed_time.to_string(),
+            ]).collect::<Vec<_>>();
+            (vec!["tx_hash", "log_index", "block_number", "block_hash", "address", "amount", "claimed_time"], rows)
+        },
+    };
+    let content = match args.format {
+        ExportFormat::Csv => {
+            let mut lines = vec![header.join(",")];
+            lines.extend(rows.iter().map(|r| r.join(",")));
+            lines.join("\n") + "\n"
+        },
+        ExportFormat::Json => {
+            let records = rows.iter()
+                .map(|r| header.iter().zip(r)
+                    .map(|(k, v)| (k.to_string(), serde_json::Value::String(v.clone())))
+                    .collect::<serde_json::Map<_, _>>())
+                .collect::<Vec<_>>();
+            serde_json::to_string_pretty(&records)?
+        },
+    };
+    fs::write(&args.out, content)?;
+    println!("{} rows written to {}", rows.len(), args.out.display());
+    Ok(())
+}
@@ -0,0 +1,50 @@
+use clap::{Args, Subcommand};
+use crate::config::Config;
+use crate::{init_db, storage};
+
+#[derive(Args)]
+pub struct MigrateArgs {
+    #[command(subcommand)]
+    pub action: MigrateAction,
+}
+
+#[derive(Subcommand)]
+pub enum MigrateAction {
+    /// Apply every pending migration.
+    Up,
+    /// Revert the last applied migrations.
+    Down {
+        #[arg(long, default_value_t = 1)]
+        steps: usize,
+    },
+    /// List the migrations and whether they are applied.
+    Status,
+}
+
+pub async fn run(config: Config, args: MigrateArgs) -> anyhow::Result<()> {
+    let rb = init_db(config.database_url.clone(), config.db_pool_size as usize);
+    match args.action {
+        MigrateAction::Up => {
+            let ran = storage::run_pending_migrations(&rb).await?;
+            if ran.is_empty() {
+                println!("no pending migration");
+            }
+            for name in ran {
+                println!("applied {}", name);
+            }
+        },
+        MigrateAction::Down { steps } => {
+            for name in storage::revert_migrations(&rb, steps).await? {
+                println!("reverted {}", name);
+            }
+        },
+        MigrateAction::Status => {
+            let applied = storage::applied_versions(&rb).await?;
+            for migration in storage::MIGRATIONS {
+                let status = if applied.iter().any(|v| v == migration.version) { "applied" } else { "pending" };
+                println!("{:<8} {}", status, migration.name);
+            }
+        },
+    }
+    Ok(())
+}
@@ -0,0 +1,49 @@
+use clap::{Parser, Subcommand};
+use rbatis::RBatis;
+use crate::cli::build_tree::BuildTreeArgs;
+use crate::cli::export::ExportArgs;
+use crate::cli::migrate::MigrateArgs;
+use crate::cli::sync::SyncArgs;
+use crate::config::Config;
+use crate::{init_db, storage};
+
+pub mod build_tree;
+pub mod export;
+pub mod migrate;
+pub mod run;
+pub mod sync;
+
+#[derive(Parser)]
+#[command(name = "pdoge", about = "The backend of pilotdoge")]
+pub struct Cli {
+    /// Runs the http server and the watcher when no command is given.
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Run the http server and the chain watcher.
+    Run,
+    /// Run only the http server.
+    Serve,
+    /// Run only the chain watcher.
+    Watch,
+    /// Sync claim events once, up to a block, then exit.
+    Sync(SyncArgs),
+    /// Build the eligible tree and export it without starting the server.
+    BuildTree(BuildTreeArgs),
+    /// Export a table to a CSV or JSON file.
+    Export(ExportArgs),
+    /// Apply, revert or list the database migrations.
+    Migrate(MigrateArgs),
+}
+
+/// Connects to the database, applying the pending migrations first unless `AUTO_MIGRATE` is false.
+pub async fn open_db(config: &Config) -> anyhow::Result<RBatis> {
+    let rb = init_db(config.database_url.clone(), config.db_pool_size as usize);
+    if config.auto_migrate {
+        storage::run_pending_migrations(&rb).await?;
+    }
+    Ok(rb)
+}
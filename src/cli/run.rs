@@ -0,0 +1,89 @@
+use std::cell::RefCell;
+use std::sync::Arc;
+use std::time::Duration;
+use futures::channel::mpsc;
+use futures::executor::block_on;
+use futures::SinkExt;
+use futures::StreamExt;
+use rbatis::RBatis;
+use tokio::sync::OnceCell;
+use crate::campaign::CampaignState;
+use crate::config::Config;
+use crate::reward::RewardEngine;
+use crate::server::AppState;
+use crate::watcher::watcher::run_watcher;
+use crate::cli::open_db;
+use crate::{eligibility, server, tree};
+
+/// Loads the reward engine and the campaign phase every long running command needs.
+pub async fn load_campaign(config: &Config, rb: &RBatis) -> anyhow::Result<(Arc<RewardEngine>, CampaignState)> {
+    let reward_engine = Arc::new(RewardEngine::from_config(config)?);
+    let campaign = CampaignState::load(config, rb.clone(), reward_engine.clone()).await?;
+    Ok((reward_engine, campaign))
+}
+
+/// Runs the http server and/or the watcher until one of them fails or ctrl+c is received.
+pub async fn run(config: Config, serve: bool, watch: bool) -> anyhow::Result<()> {
+    if watch {
+        config.watcher.validate()?;
+    }
+    let rb = open_db(&config).await?;
+    let (reward_engine, campaign) = load_campaign(&config, &rb).await?;
+
+    if serve {
+        let eligible_tree = Arc::new(OnceCell::new());
+        if campaign.current().is_tree_frozen() {
+            let tree = tree::load_eligible_tree(&rb, config.merkle_snapshot_version).await?;
+            eligible_tree.set(Arc::new(tree)).expect("eligible tree is only set once");
+        }
+        let eligibility_provider = eligibility::provider_from_config(&config)?;
+        let app_state = AppState {
+            config: config.clone(),
+            db: rb.clone(),
+            campaign: campaign.clone(),
+            eligible_tree,
+            eligibility_provider,
+            reward_engine,
+        };
+        server::run_server(app_state).await;
+        if !watch {
+            // the phase schedule is applied by the watcher process
+            tokio::spawn(campaign.clone().run_refresher(Duration::from_secs(10)));
+        }
+    }
+
+    let watcher_handler = if watch {
+        Some(run_watcher(config.clone(), rb.clone(), campaign).await?)
+    } else {
+        None
+    };
+
+    // handle ctrl+c
+    let (stop_signal_sender, mut stop_signal_receiver) = mpsc::channel(256);
+    {
+        let stop_signal_sender = RefCell::new(stop_signal_sender.clone());
+        ctrlc::set_handler(move || {
+            let mut sender = stop_signal_sender.borrow_mut();
+            block_on(sender.send(true)).expect("Ctrl+C signal send");
+        })
+            .expect("Error setting Ctrl+C handler");
+    }
+
+    let watcher_finished = async {
+        match watcher_handler {
+            Some(handler) => handler.await,
+            None => futures::future::pending().await,
+        }
+    };
+    tokio::select! {
+        Err(e) = watcher_finished => {
+            if e.is_panic() { log::error!("The one of watcher actors unexpectedly panic:{}", e) }
+            log::error!("Watchers actors aren't supposed to finish any of their execution")
+        },
+        _ = async { stop_signal_receiver.next().await } => {
+            log::warn!("Stop signal received, shutting down");
+        }
+    };
+
+    Ok(())
+}
@@ -0,0 +1,29 @@
+use clap::Args;
+use crate::cli::run::load_campaign;
+use crate::config::Config;
+use crate::cli::open_db;
+use crate::watcher::watcher::ChainWatcher;
+
+#[derive(Args)]
+pub struct SyncArgs {
+    /// Last block to sync, the chain head if not given.
+    #[arg(long)]
+    pub to_block: Option<u64>,
+    /// Sync the blocks already synced again from this one first, to index the events of the handlers
+    /// added since. The sync checkpoint does not move back.
+    #[arg(long)]
+    pub from: Option<u64>,
+}
+
+pub async fn run(config: Config, args: SyncArgs) -> anyhow::Result<()> {
+    let rb = open_db(&config).await?;
+    let (_, campaign) = load_campaign(&config, &rb).await?;
+    let mut watcher = ChainWatcher::new(config, rb, campaign).await?;
+    if let Some(from) = args.from {
+        watcher.run_backfill(from, args.to_block).await?;
+        log::info!("backfill finished");
+    }
+    watcher.run_sync_events(args.to_block).await?;
+    log::info!("sync finished");
+    Ok(())
+}
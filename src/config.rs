@@ -1,51 +1,322 @@
 use std::env;
+use std::str::FromStr;
+use anyhow::format_err;
+use crate::address::AccountAddress;
+use crate::amount::Rounding;
+
+/// Most addresses `BATCH_MAX_ADDRESSES` may allow.
+pub const BATCH_MAX_ADDRESSES_LIMIT: usize = 1000;
+
 #[derive(Debug,Clone)]
 pub struct Config {
     pub port: u16,
     pub workers: u16,
     pub tokens_number_per_gas: String,
     pub eligible_min_gas: String,
+    pub reward_formula: String,
+    pub reward_formula_path: String,
+    pub total_budget: String,
     pub token_address: String,
     pub token_decimal: u32,
+    pub amount_rounding: Rounding,
     pub database_url: String,
     pub db_pool_size: u16,
-    pub remote_web3_url: String,
-    pub sync_start_block: u64,
-    pub claim_start: bool,
+    pub auto_migrate: bool,
+    pub watcher: WatcherConfig,
+    pub campaign_phase: String,
+    pub phase_snapshot_frozen_at: String,
+    pub phase_claiming_at: String,
+    pub phase_ended_at: String,
+    pub merkle_snapshot_version: Option<i64>,
+    pub eligibility_provider: String,
+    pub orbiter_api_url: String,
+    pub eligibility_snapshot_path: String,
+    /// Bearer token of the admin endpoints, which are disabled when it is empty.
+    pub admin_token: String,
+    /// Most addresses a batch request may ask for.
+    pub batch_max_addresses: usize,
+    /// Blocks after their claim in which the tokens claimers send away count in the holder stats,
+    /// every later block if `None`.
+    pub holder_stats_window_blocks: Option<u64>,
 }
 
 impl Config {
-    pub fn from_env() ->Self {
+    /// Reads the settings from the environment, failing on the ones which can't be ignored if malformed.
+    pub fn from_env() -> anyhow::Result<Self> {
         let port = env::var("SERVER_PORT").unwrap_or_default()
             .parse::<u16>().unwrap_or(8088u16);
         let workers = env::var("WORKERS_NUMBER").unwrap_or_default()
             .parse::<u16>().unwrap_or(2u16);
         let tokens_number_per_gas = env::var("TOKENS_NUMBER_PER_GAS").unwrap_or_default();
         let eligible_min_gas = env::var("ELIGIBLE_MIN_GAS").unwrap_or_default();
+        let reward_formula = env::var("REWARD_FORMULA").unwrap_or_default();
+        let reward_formula_path = env::var("REWARD_FORMULA_PATH").unwrap_or_default();
+        let total_budget = env::var("TOTAL_BUDGET").unwrap_or_default();
         let token_address = env::var("TOKEN_ADDRESS").unwrap_or_default();
 
         let database_url = env::var("DATABASE_URL").unwrap_or_default();
-        let remote_web3_url = env::var("REMOTE_WEB3_URL").unwrap_or_default();
         let db_pool_size = env::var("DB_POOL_SIZE").unwrap_or_default()
             .parse::<u16>().unwrap_or(1u16);
-        let sync_start_block = env::var("SYNC_START_BLOCK").unwrap_or_default()
-            .parse::<u64>().unwrap_or(0u64);
+        let auto_migrate = env::var("AUTO_MIGRATE").unwrap_or_default()
+            .parse::<bool>().unwrap_or(true);
+        let watcher = WatcherConfig::from_env(&token_address);
         let token_decimal = env::var("TOKEN_DECIMAL").unwrap_or_default()
             .parse::<u32>().unwrap_or(0u32);
-        let claim_start = env::var("CLAIM_START").unwrap_or_default()
-            .parse::<bool>().unwrap_or(false);
-        Self {
+        let amount_rounding = match env::var("AMOUNT_ROUNDING") {
+            Ok(rounding) if !rounding.trim().is_empty() => rounding.parse::<Rounding>()
+                .map_err(|e| format_err!("invalid AMOUNT_ROUNDING {},{:?}", rounding, e))?,
+            _ => Rounding::Down,
+        };
+        let campaign_phase = env::var("CAMPAIGN_PHASE").unwrap_or_default();
+        let phase_snapshot_frozen_at = env::var("PHASE_SNAPSHOT_FROZEN_AT").unwrap_or_default();
+        let phase_claiming_at = env::var("PHASE_CLAIMING_AT").unwrap_or_default();
+        let phase_ended_at = env::var("PHASE_ENDED_AT").unwrap_or_default();
+        let merkle_snapshot_version = match env::var("MERKLE_SNAPSHOT_VERSION") {
+            Ok(version) if !version.trim().is_empty() => Some(version.trim().parse::<i64>()
+                .map_err(|e| format_err!("invalid MERKLE_SNAPSHOT_VERSION {},{:?}", version, e))?),
+            _ => None,
+        };
+        let eligibility_provider = env::var("ELIGIBILITY_PROVIDER")
+            .unwrap_or_else(|_| "orbiter".to_string());
+        let orbiter_api_url = env::var("ORBITER_API_URL")
+            .unwrap_or_else(|_| "https://openapi.orbiter.finance/mainnet/v1/gas".to_string());
+        let eligibility_snapshot_path = env::var("ELIGIBILITY_SNAPSHOT_PATH").unwrap_or_default();
+        let admin_token = env::var("ADMIN_TOKEN").unwrap_or_default();
+        let batch_max_addresses = match env::var("BATCH_MAX_ADDRESSES") {
+            Ok(max) if !max.trim().is_empty() => max.trim().parse::<usize>().ok()
+                .filter(|max| (1..=BATCH_MAX_ADDRESSES_LIMIT).contains(max))
+                .ok_or_else(|| format_err!("invalid BATCH_MAX_ADDRESSES {}, expected 1 to {}", max, BATCH_MAX_ADDRESSES_LIMIT))?,
+            _ => 100usize,
+        };
+        let holder_stats_window_blocks = match env::var("HOLDER_STATS_WINDOW_BLOCKS") {
+            Ok(window) if !window.trim().is_empty() => Some(window.trim().parse::<u64>()
+                .map_err(|e| format_err!("invalid HOLDER_STATS_WINDOW_BLOCKS {},{:?}", window, e))?),
+            _ => None,
+        };
+        Ok(Self {
             port,
             workers,
             tokens_number_per_gas,
             eligible_min_gas,
+            reward_formula,
+            reward_formula_path,
+            total_budget,
             token_address,
             token_decimal,
+            amount_rounding,
             database_url,
             db_pool_size,
-            remote_web3_url,
+            auto_migrate,
+            watcher,
+            campaign_phase,
+            phase_snapshot_frozen_at,
+            phase_claiming_at,
+            phase_ended_at,
+            merkle_snapshot_version,
+            eligibility_provider,
+            orbiter_api_url,
+            eligibility_snapshot_path,
+            admin_token,
+            batch_max_addresses,
+            holder_stats_window_blocks,
+        })
+    }
+}
+
+/// Settings of the chain watcher, see [`WatcherConfig::validate`] for their constraints.
+#[derive(Debug,Clone)]
+pub struct WatcherConfig {
+    /// HTTP RPC endpoints, tried in order of health.
+    pub remote_web3_urls: Vec<String>,
+    /// WebSocket endpoint whose new heads trigger a sync and whose `Claimed` logs are saved unconfirmed,
+    /// polling only if empty.
+    pub remote_web3_ws_url: String,
+    pub rpc_timeout_secs: u64,
+    pub rpc_max_retries: u32,
+    pub rpc_backoff_ms: u64,
+    /// RPC requests in flight at once, across every endpoint.
+    pub rpc_max_concurrent_requests: usize,
+    pub poll_interval_secs: u64,
+    pub sync_start_block: u64,
+    /// Blocks per `eth_getLogs` request at startup, adapted between `sync_min_step` and `sync_max_step`.
+    pub sync_step: u64,
+    pub sync_min_step: u64,
+    pub sync_max_step: u64,
+    pub confirmations: u64,
+    /// The distributor contract emitting the claim events.
+    pub contract_address: String,
+    /// The token whose transfers are indexed when `index_token_transfers` is set.
+    pub token_address: String,
+    pub index_token_transfers: bool,
+    /// The variables whose value could not be parsed, reported by `validate`.
+    invalid: Vec<String>,
+}
+
+impl WatcherConfig {
+    pub fn from_env(token_address: &str) -> Self {
+        // REMOTE_WEB3_URLS is a comma separated list, REMOTE_WEB3_URL a single endpoint
+        let remote_web3_urls = env::var("REMOTE_WEB3_URLS")
+            .or_else(|_| env::var("REMOTE_WEB3_URL"))
+            .unwrap_or_default()
+            .split(',')
+            .map(|url| url.trim().to_string())
+            .filter(|url| !url.is_empty())
+            .collect::<Vec<_>>();
+        let remote_web3_ws_url = env::var("REMOTE_WEB3_WS_URL").unwrap_or_default();
+        let mut invalid = vec![];
+        let rpc_timeout_secs = parse_env("RPC_TIMEOUT_SECS", 30u64, &mut invalid);
+        let rpc_max_retries = parse_env("RPC_MAX_RETRIES", 5u32, &mut invalid);
+        let rpc_backoff_ms = parse_env("RPC_BACKOFF_MS", 500u64, &mut invalid);
+        let rpc_max_concurrent_requests = parse_env("RPC_MAX_CONCURRENT_REQUESTS", 4usize, &mut invalid);
+        let poll_interval_secs = parse_env("WATCHER_POLL_INTERVAL_SECS", 120u64, &mut invalid);
+        let sync_start_block = parse_env("SYNC_START_BLOCK", 0u64, &mut invalid);
+        let sync_step = parse_env("SYNC_STEP", 1000u64, &mut invalid);
+        let sync_min_step = parse_env("SYNC_MIN_STEP", 10u64, &mut invalid);
+        let sync_max_step = parse_env("SYNC_MAX_STEP", 10000u64, &mut invalid);
+        let confirmations = parse_env("CONFIRMATIONS", 12u64, &mut invalid);
+        // the token contract used to emit the claim events before the distributor was split from it
+        let contract_address = env::var("DISTRIBUTOR_ADDRESS")
+            .unwrap_or_else(|_| token_address.to_string());
+        let index_token_transfers = parse_env("INDEX_TOKEN_TRANSFERS", true, &mut invalid);
+        Self {
+            remote_web3_urls,
+            remote_web3_ws_url,
+            rpc_timeout_secs,
+            rpc_max_retries,
+            rpc_backoff_ms,
+            rpc_max_concurrent_requests,
+            poll_interval_secs,
             sync_start_block,
-            claim_start
+            sync_step,
+            sync_min_step,
+            sync_max_step,
+            confirmations,
+            contract_address,
+            token_address: token_address.to_string(),
+            index_token_transfers,
+            invalid,
         }
     }
-}
\ No newline at end of file
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if !self.invalid.is_empty() {
+            return Err(format_err!("malformed watcher settings: {}", self.invalid.join(", ")));
+        }
+        if self.remote_web3_urls.is_empty() {
+            return Err(format_err!("no RPC endpoint configured, set REMOTE_WEB3_URLS or REMOTE_WEB3_URL"));
+        }
+        for url in &self.remote_web3_urls {
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                return Err(format_err!("RPC endpoint {} is not an http(s) url", url));
+            }
+        }
+        if !self.remote_web3_ws_url.is_empty()
+            && !self.remote_web3_ws_url.starts_with("ws://") && !self.remote_web3_ws_url.starts_with("wss://") {
+            return Err(format_err!("REMOTE_WEB3_WS_URL {} is not a ws(s) url", self.remote_web3_ws_url));
+        }
+        AccountAddress::from_str(&self.contract_address)
+            .map_err(|e| format_err!("invalid DISTRIBUTOR_ADDRESS {},{:?}", self.contract_address, e))?;
+        if self.index_token_transfers {
+            AccountAddress::from_str(&self.token_address)
+                .map_err(|e| format_err!("invalid TOKEN_ADDRESS {},{:?}", self.token_address, e))?;
+        }
+        if self.poll_interval_secs == 0 {
+            return Err(format_err!("WATCHER_POLL_INTERVAL_SECS must be positive"));
+        }
+        if self.rpc_timeout_secs == 0 {
+            return Err(format_err!("RPC_TIMEOUT_SECS must be positive"));
+        }
+        if self.rpc_max_concurrent_requests == 0 {
+            return Err(format_err!("RPC_MAX_CONCURRENT_REQUESTS must be positive"));
+        }
+        if self.sync_min_step == 0 || self.sync_min_step > self.sync_step || self.sync_step > self.sync_max_step {
+            return Err(format_err!("sync steps must satisfy 0 < SYNC_MIN_STEP ({}) <= SYNC_STEP ({}) <= SYNC_MAX_STEP ({})",
+                self.sync_min_step, self.sync_step, self.sync_max_step));
+        }
+        Ok(())
+    }
+}
+
+/// Parses the variable `name`, `default` if it is unset or empty. Malformed values are added to `invalid`.
+fn parse_env<T: FromStr>(name: &str, default: T, invalid: &mut Vec<String>) -> T {
+    match env::var(name) {
+        Ok(value) if !value.is_empty() => value.parse::<T>().unwrap_or_else(|_| {
+            invalid.push(format!("{}={}", name, value));
+            default
+        }),
+        _ => default,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn config() -> WatcherConfig {
+        WatcherConfig {
+            remote_web3_urls: vec!["https://rpc.example".to_string()],
+            remote_web3_ws_url: "wss://rpc.example".to_string(),
+            rpc_timeout_secs: 30,
+            rpc_max_retries: 5,
+            rpc_backoff_ms: 500,
+            rpc_max_concurrent_requests: 4,
+            poll_interval_secs: 120,
+            sync_start_block: 0,
+            sync_step: 1000,
+            sync_min_step: 10,
+            sync_max_step: 10000,
+            confirmations: 12,
+            contract_address: "0x5555555555555555555555555555555555555555".to_string(),
+            token_address: "0x6666666666666666666666666666666666666666".to_string(),
+            index_token_transfers: true,
+            invalid: vec![],
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(config().validate().is_ok());
+        let empty_ws = WatcherConfig { remote_web3_ws_url: "".to_string(), ..config() };
+        assert!(empty_ws.validate().is_ok());
+        let invalid = WatcherConfig { invalid: vec!["CONFIRMATIONS=abc".to_string()], ..config() };
+        assert!(invalid.validate().unwrap_err().to_string().contains("CONFIRMATIONS=abc"));
+    }
+
+    #[test]
+    fn test_validate_steps() {
+        let steps = |sync_min_step, sync_step, sync_max_step| WatcherConfig { sync_min_step, sync_step, sync_max_step, ..config() };
+        assert!(steps(10, 10, 10).validate().is_ok());
+        assert!(steps(0, 10, 100).validate().is_err());
+        assert!(steps(20, 10, 100).validate().is_err());
+        assert!(steps(10, 200, 100).validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_urls() {
+        assert!(WatcherConfig { remote_web3_urls: vec![], ..config() }.validate().is_err());
+        assert!(WatcherConfig { remote_web3_urls: vec!["ws://rpc.example".to_string()], ..config() }.validate().is_err());
+        assert!(WatcherConfig { remote_web3_urls: vec!["http://a".to_string(), "b".to_string()], ..config() }.validate().is_err());
+        assert!(WatcherConfig { remote_web3_ws_url: "https://rpc.example".to_string(), ..config() }.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_addresses() {
+        assert!(WatcherConfig { contract_address: "0x1234".to_string(), ..config() }.validate().is_err());
+        let token_address = "not an address".to_string();
+        assert!(WatcherConfig { token_address: token_address.clone(), ..config() }.validate().is_err());
+        assert!(WatcherConfig { token_address, index_token_transfers: false, ..config() }.validate().is_ok());
+    }
+
+    #[test]
+    fn test_parse_env() {
+        let mut invalid = vec![];
+        env::set_var("TEST_PARSE_ENV_NUMBER", "1k");
+        env::set_var("TEST_PARSE_ENV_EMPTY", "");
+        assert_eq!(parse_env("TEST_PARSE_ENV_NUMBER", 1000u64, &mut invalid), 1000);
+        assert_eq!(parse_env("TEST_PARSE_ENV_EMPTY", 10u64, &mut invalid), 10);
+        assert!(parse_env("TEST_PARSE_ENV_UNSET", true, &mut invalid));
+        assert_eq!(invalid, vec!["TEST_PARSE_ENV_NUMBER=1k".to_string()]);
+        env::set_var("TEST_PARSE_ENV_NUMBER", "12");
+        assert_eq!(parse_env("TEST_PARSE_ENV_NUMBER", 1000u64, &mut invalid), 12);
+    }
+}
@@ -1,23 +1,33 @@
 use rbatis::rbdc::decimal::Decimal;
 use std::str::FromStr;
-use web3::types::H160;
-use crate::watcher::event::ClaimEvent;
+use crate::address::AccountAddress;
+use crate::watcher::event::{ClaimEvent, TransferEvent};
 
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct LastSyncBlock {
     pub block_number: i64,
 }
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct SyncedBlock {
+    pub block_number: i64,
+    pub block_hash: String,
+}
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct QueryAccount {
     pub address: String,
     pub claimable_amount: Decimal,
     pub query_time: i64,
+    pub eth_gas_cost: Option<Decimal>,
+    /// false for the rows queried when amounts were in whole tokens
+    pub base_units: bool,
 }
 
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct AccountEligible {
     pub address: String,
     pub claimable_amount: String,
+    pub eth_gas_cost: Option<String>,
+    pub base_units: bool,
 }
 
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
@@ -27,25 +37,237 @@ pub struct ClaimedAccount {
     pub claimed_amount: Decimal,
 }
 
-rbatis::crud!(QueryAccount {}, "query_accounts");
-rbatis::crud!(ClaimedAccount {}, "claimed_accounts");
-rbatis::crud!(LastSyncBlock {}, "last_sync_block");
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct ClaimedAddress {
+    pub address: String,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct ClaimEventRecord {
+    pub tx_hash: String,
+    pub log_index: i64,
+    pub block_number: i64,
+    pub block_hash: String,
+    pub address: String,
+    pub amount: Decimal,
+    pub claimed_time: i64,
+    /// false until the sync reaches the block of a claim received from the `Claimed` logs subscription
+    pub confirmed: bool,
+    /// Whether the anomalies of the claim were looked for, once confirmed.
+    pub checked: bool,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct DistributorEvent {
+    pub tx_hash: String,
+    pub log_index: i64,
+    pub block_number: i64,
+    pub block_hash: String,
+    pub event_name: String,
+    /// JSON object of the decoded event parameters.
+    pub params: String,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct TokenTransfer {
+    pub tx_hash: String,
+    pub log_index: i64,
+    pub block_number: i64,
+    pub block_hash: String,
+    pub from_address: String,
+    pub to_address: String,
+    pub amount: Decimal,
+}
+
+/// What is recorded about an account, every field is `None` for an account never queried nor claimed.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct AccountDetail {
+    pub claimable_amount: Option<Decimal>,
+    /// false when `claimable_amount` is in whole tokens, see [`QueryAccount::base_units`].
+    pub base_units: Option<bool>,
+    pub query_time: Option<i64>,
+    pub claimed_time: Option<i64>,
+    pub claimed_amount: Option<Decimal>,
+    /// Transaction of the first claim of the account.
+    pub claim_tx_hash: Option<String>,
+}
+
+/// An indexed claim which doesn't match the eligible tree.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct ClaimAnomaly {
+    pub tx_hash: String,
+    pub log_index: i64,
+    /// `unknown_address`, `wrong_amount` or `double_claim`.
+    pub kind: String,
+    pub block_number: i64,
+    pub address: String,
+    pub claimed_amount: Decimal,
+    /// Amount of the address leaf, if it has one.
+    pub expected_amount: Option<Decimal>,
+    /// Version of the snapshot the claim was checked against.
+    pub snapshot_version: i64,
+    pub detected_at: i64,
+}
+
+/// A claimer with the tokens they sent away after their first claim.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct ClaimerOutflow {
+    pub address: String,
+    pub claimed_amount: Decimal,
+    pub outflow: Decimal,
+}
+
+/// How much of their claimed tokens the claimers sent away after claiming.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct HolderStats {
+    pub claimers: i64,
+    /// Claimers who sent nothing away.
+    pub retained: i64,
+    /// Claimers who sent away less than their claimed amount.
+    pub partially_sold: i64,
+    /// Claimers who sent away at least their claimed amount.
+    pub sold: i64,
+    pub claimed_amount: Decimal,
+    /// Claimed tokens not sent away, counting each claimer up to their claimed amount.
+    pub retained_amount: Decimal,
+}
+
+impl HolderStats {
+    pub fn of(claimers: &[ClaimerOutflow]) -> Self {
+        let zero = Decimal::from_str("0").unwrap();
+        let mut stats = HolderStats {
+            claimers: claimers.len() as i64,
+            retained: 0,
+            partially_sold: 0,
+            sold: 0,
+            claimed_amount: zero.clone(),
+            retained_amount: zero.clone(),
+        };
+        let zero = zero.0;
+        for claimer in claimers {
+            let (claimed, outflow) = (&claimer.claimed_amount.0, &claimer.outflow.0);
+            if *outflow <= zero {
+                stats.retained += 1;
+            } else if outflow < claimed {
+                stats.partially_sold += 1;
+            } else {
+                stats.sold += 1;
+            }
+            stats.claimed_amount.0 += claimed;
+            if outflow < claimed {
+                stats.retained_amount.0 += claimed - outflow.max(&zero);
+            }
+        }
+        stats
+    }
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct CampaignPhaseRow {
+    pub phase: String,
+    pub updated_at: i64,
+    /// The snapshot frozen when the campaign entered `SnapshotFrozen`.
+    pub snapshot_version: Option<i64>,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct MerkleSnapshot {
+    pub version: i64,
+    pub root: String,
+    /// JSON array of the leaf value types.
+    pub leaf_encoding: String,
+    /// JSON array of the leaf values, in tree value order.
+    pub leaves: String,
+    pub created_at: i64,
+    pub total_amount: Option<Decimal>,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct SchemaMigration {
+    pub version: String,
+}
 
+rbatis::impl_select!(QueryAccount {}, "query_accounts");
+rbatis::impl_select!(ClaimedAccount {}, "claimed_accounts");
+rbatis::impl_select!(ClaimEventRecord {}, "claim_events");
+rbatis::impl_select!(LastSyncBlock {}, "last_sync_block");
+
+impl From<TransferEvent> for TokenTransfer {
+    fn from(event: TransferEvent) -> Self {
+        Self {
+            tx_hash: format!("{:?}", event.position.tx_hash),
+            log_index: event.position.log_index as i64,
+            block_number: event.position.block_number as i64,
+            block_hash: format!("{:?}", event.position.block_hash),
+            from_address: AccountAddress::from(event.from).to_string(),
+            to_address: AccountAddress::from(event.to).to_string(),
+            amount: Decimal::from_str(&event.value.to_string()).unwrap_or(Decimal::from_str("0").unwrap()),
+        }
+    }
+}
 impl Default for QueryAccount {
     fn default() -> Self {
         QueryAccount {
-            address: H160::zero().to_string(),
+            address: AccountAddress::default().to_string(),
             claimable_amount: Decimal::from_str("0").unwrap(),
             query_time: 0,
+            eth_gas_cost: None,
+            base_units: true,
         }
     }
 }
-impl From<ClaimEvent> for ClaimedAccount {
+impl From<ClaimEvent> for ClaimEventRecord {
     fn from(event: ClaimEvent) -> Self {
         Self {
-            address: event.address.to_string(),
+            tx_hash: format!("{:?}", event.tx_hash),
+            log_index: event.log_index as i64,
+            block_number: event.block_number as i64,
+            block_hash: format!("{:?}", event.block_hash),
+            address: AccountAddress::from(event.address).to_string(),
+            amount: Decimal::from_str(&event.amount.to_string()).unwrap_or(Decimal::from_str("0").unwrap()),
             claimed_time: event.claimed_time.as_u64() as i64,
-            claimed_amount: Decimal::from_str(&event.amount.to_string()).unwrap_or(Decimal::from_str("0").unwrap()),
+            confirmed: true,
+            checked: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn claimer(claimed_amount: &str, outflow: &str) -> ClaimerOutflow {
+        ClaimerOutflow {
+            address: AccountAddress::default().to_string(),
+            claimed_amount: Decimal::from_str(claimed_amount).unwrap(),
+            outflow: Decimal::from_str(outflow).unwrap(),
         }
     }
+
+    #[test]
+    fn test_holder_stats() {
+        let stats = HolderStats::of(&[
+            claimer("100", "0"),
+            claimer("100", "40"),
+            claimer("100", "100"),
+            // tokens bought after the claim and sold are counted up to the claimed amount
+            claimer("100", "250"),
+        ]);
+        assert_eq!(stats, HolderStats {
+            claimers: 4,
+            retained: 1,
+            partially_sold: 1,
+            sold: 2,
+            claimed_amount: Decimal::from_str("400").unwrap(),
+            retained_amount: Decimal::from_str("160").unwrap(),
+        });
+    }
+
+    #[test]
+    fn test_holder_stats_without_claimers() {
+        let stats = HolderStats::of(&[]);
+        assert_eq!((stats.claimers, stats.retained), (0, 0));
+        assert_eq!(stats.claimed_amount, Decimal::from_str("0").unwrap());
+        assert_eq!(stats.retained_amount, Decimal::from_str("0").unwrap());
+    }
 }
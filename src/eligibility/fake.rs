@@ -0,0 +1,32 @@
+use std::collections::HashMap;
+use anyhow::format_err;
+use async_trait::async_trait;
+use bigdecimal::BigDecimal;
+use crate::address::AccountAddress;
+use crate::eligibility::EligibilityProvider;
+
+/// An in-memory provider standing in for the real data sources in tests.
+#[derive(Default)]
+pub struct FakeProvider {
+    pub gas: HashMap<AccountAddress, BigDecimal>,
+    pub amounts: HashMap<AccountAddress, BigDecimal>,
+    /// Every query fails when set, like an unreachable source.
+    pub unavailable: bool,
+}
+
+#[async_trait]
+impl EligibilityProvider for FakeProvider {
+    async fn gas_spent(&self, address: &AccountAddress) -> anyhow::Result<Option<BigDecimal>> {
+        if self.unavailable {
+            return Err(format_err!("fake provider unavailable"));
+        }
+        Ok(self.gas.get(address).cloned())
+    }
+
+    async fn claimable_amount(&self, address: &AccountAddress) -> anyhow::Result<Option<BigDecimal>> {
+        if self.unavailable {
+            return Err(format_err!("fake provider unavailable"));
+        }
+        Ok(self.amounts.get(address).cloned())
+    }
+}
@@ -0,0 +1,35 @@
+use std::sync::Arc;
+use anyhow::format_err;
+use async_trait::async_trait;
+use bigdecimal::BigDecimal;
+use crate::address::AccountAddress;
+use crate::config::Config;
+use crate::eligibility::orbiter::OrbiterProvider;
+use crate::eligibility::snapshot::SnapshotProvider;
+
+#[cfg(test)]
+pub mod fake;
+pub mod orbiter;
+pub mod snapshot;
+
+/// A data source answering how much gas an address has spent, which the
+/// claimable amount is derived from.
+#[async_trait]
+pub trait EligibilityProvider: Send + Sync {
+    /// Returns the ETH gas spent by `address`, or `None` if the source has no record of it.
+    async fn gas_spent(&self, address: &AccountAddress) -> anyhow::Result<Option<BigDecimal>>;
+
+    /// Returns a pre-computed claimable amount in whole tokens for `address` that overrides the amount
+    /// derived from gas, for sources that already carry final allocations.
+    async fn claimable_amount(&self, _address: &AccountAddress) -> anyhow::Result<Option<BigDecimal>> {
+        Ok(None)
+    }
+}
+
+pub fn provider_from_config(config: &Config) -> anyhow::Result<Arc<dyn EligibilityProvider>> {
+    match config.eligibility_provider.as_str() {
+        "orbiter" => Ok(Arc::new(OrbiterProvider::new(config.orbiter_api_url.clone()))),
+        "snapshot" => Ok(Arc::new(SnapshotProvider::load(&config.eligibility_snapshot_path)?)),
+        other => Err(format_err!("unknown eligibility provider: {}", other)),
+    }
+}
@@ -0,0 +1,52 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use anyhow::format_err;
+use async_trait::async_trait;
+use bigdecimal::BigDecimal;
+use serde::{Deserialize, Serialize};
+use crate::address::AccountAddress;
+use crate::eligibility::EligibilityProvider;
+
+#[derive(Clone,Debug,Serialize,Deserialize)]
+pub struct EligibleResult {
+    pub count: u32,
+    pub gas: HashMap<String,String>,
+}
+#[derive(Clone,Debug,Serialize,Deserialize)]
+pub struct OrbiterEligibleResp {
+    pub code: u32,
+    pub msg: String,
+    pub result: EligibleResult,
+}
+
+/// Queries the gas an address spent through Orbiter bridges from the Orbiter open api.
+pub struct OrbiterProvider {
+    base_url: String,
+    client: reqwest::Client,
+}
+
+impl OrbiterProvider {
+    pub fn new(base_url: String) -> Self {
+        Self {
+            base_url,
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl EligibilityProvider for OrbiterProvider {
+    async fn gas_spent(&self, address: &AccountAddress) -> anyhow::Result<Option<BigDecimal>> {
+        let url = format!("{}?address={}", self.base_url, address);
+        let resp = self.client.get(url).send().await
+            .map_err(|e| format_err!("Orbiter api connected failed,{:?}", e))?;
+        if !resp.status().is_success() {
+            return Err(format_err!("Orbiter api return failed,status {}", resp.status()));
+        }
+        let ret = resp.text().await?;
+        let eligible_ret: OrbiterEligibleResp = serde_json::from_str(&ret)?;
+        let gas_eth_cost = eligible_ret.result.gas.get("ETH")
+            .map(|gas| BigDecimal::from_str(gas).unwrap_or_default());
+        Ok(gas_eth_cost)
+    }
+}
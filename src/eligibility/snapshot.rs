@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use anyhow::format_err;
+use async_trait::async_trait;
+use bigdecimal::BigDecimal;
+use num::BigInt;
+use crate::address::AccountAddress;
+use crate::eligibility::EligibilityProvider;
+use crate::reward::RewardEngine;
+
+/// What the value column of a snapshot holds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SnapshotKind {
+    /// ETH gas spent, the claimable amount is computed from it as for live sources.
+    Gas,
+    /// A pre-computed claimable amount in whole tokens, only scaled to base units.
+    Amount,
+}
+
+impl FromStr for SnapshotKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "gas" => Ok(SnapshotKind::Gas),
+            "amount" => Ok(SnapshotKind::Amount),
+            other => Err(format_err!("unknown snapshot column: {}", other)),
+        }
+    }
+}
+
+/// Answers eligibility queries from an in-memory snapshot loaded once at startup.
+///
+/// CSV snapshots have an `address,gas` or `address,amount` header line, JSON snapshots
+/// are an array of `{"address": .., "gas": ..}` or `{"address": .., "amount": ..}` objects.
+pub struct SnapshotProvider {
+    kind: SnapshotKind,
+    values: HashMap<AccountAddress, BigDecimal>,
+}
+
+impl SnapshotProvider {
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format_err!("read snapshot {} failed,{:?}", path, e))?;
+        let is_json = Path::new(path).extension()
+            .map(|ext| ext.eq_ignore_ascii_case("json"))
+            .unwrap_or(false);
+        let provider = if is_json {
+            Self::from_json(&content)?
+        } else {
+            Self::from_csv(&content)?
+        };
+        log::info!("loaded {} accounts from eligibility snapshot {}", provider.values.len(), path);
+        Ok(provider)
+    }
+
+    pub fn from_csv(content: &str) -> anyhow::Result<Self> {
+        let mut lines = content.lines()
+            .map(|l| l.trim())
+            .filter(|l| !l.is_empty());
+        let header = lines.next().ok_or_else(|| format_err!("snapshot is empty"))?;
+        let columns = header.split(',').map(|c| c.trim().to_lowercase()).collect::<Vec<_>>();
+        if columns.len() != 2 || columns[0] != "address" {
+            return Err(format_err!("invalid snapshot header: {}", header));
+        }
+        let kind = SnapshotKind::from_str(&columns[1])?;
+        let mut values = HashMap::new();
+        for (i, line) in lines.enumerate() {
+            let (address, value) = line.split_once(',')
+                .ok_or_else(|| format_err!("invalid snapshot line {}: {}", i + 2, line))?;
+            let value = BigDecimal::from_str(value.trim())
+                .map_err(|e| format_err!("invalid snapshot value at line {}: {:?}", i + 2, e))?;
+            let address = AccountAddress::from_str(address)
+                .map_err(|e| format_err!("invalid snapshot address at line {}: {:?}", i + 2, e))?;
+            if values.insert(address, value).is_some() {
+                return Err(format_err!("duplicate snapshot address at line {}: {}", i + 2, address));
+            }
+        }
+        Ok(Self { kind, values })
+    }
+
+    pub fn from_json(content: &str) -> anyhow::Result<Self> {
+        let records: Vec<HashMap<String, serde_json::Value>> = serde_json::from_str(content)?;
+        // every record must have the same value key as the first one, and nothing else
+        let mut kind = None;
+        let mut values = HashMap::new();
+        for (i, record) in records.iter().enumerate() {
+            let address = record.get("address").and_then(|a| a.as_str())
+                .ok_or_else(|| format_err!("snapshot record {} has no address", i))?;
+            let (value_key, value) = match (record.get("gas"), record.get("amount")) {
+                (Some(gas), None) => ("gas", gas),
+                (None, Some(amount)) => ("amount", amount),
+                _ => return Err(format_err!("snapshot record {} must have either gas or amount", i)),
+            };
+            if record.len() != 2 {
+                return Err(format_err!("snapshot record {} has unknown fields", i));
+            }
+            let record_kind = SnapshotKind::from_str(value_key)?;
+            if *kind.get_or_insert(record_kind) != record_kind {
+                return Err(format_err!("snapshot record {} has {} but the previous records don't", i, value_key));
+            }
+            let value = match value {
+                serde_json::Value::String(v) => BigDecimal::from_str(v)
+                    .map_err(|e| format_err!("invalid snapshot value in record {}: {:?}", i, e))?,
+                serde_json::Value::Number(v) => BigDecimal::from_str(&v.to_string())
+                    .map_err(|e| format_err!("invalid snapshot value in record {}: {:?}", i, e))?,
+                _ => return Err(format_err!("snapshot record {} {} is not a number", i, value_key)),
+            };
+            let address = AccountAddress::from_str(address)
+                .map_err(|e| format_err!("invalid snapshot address in record {}: {:?}", i, e))?;
+            if values.insert(address, value).is_some() {
+                return Err(format_err!("duplicate snapshot address in record {}: {}", i, address));
+            }
+        }
+        Ok(Self { kind: kind.unwrap_or(SnapshotKind::Gas), values })
+    }
+
+    /// Allocations of every account of the snapshot in base units, in address order.
+    pub fn allocations(&self, engine: &RewardEngine) -> Vec<(String, BigInt)> {
+        let mut allocations = self.values.iter()
+            .map(|(address, value)| {
+                let amount = match self.kind {
+                    SnapshotKind::Gas => engine.claimable_amount(value),
+                    SnapshotKind::Amount => engine.scale(value),
+                };
+                (*address, amount.base_units)
+            })
+            .collect::<Vec<_>>();
+        allocations.sort_by_key(|(address, _)| *address);
+        allocations.into_iter()
+            .map(|(address, amount)| (address.to_string(), amount))
+            .collect()
+    }
+
+    fn lookup(&self, address: &AccountAddress, kind: SnapshotKind) -> Option<BigDecimal> {
+        if self.kind != kind {
+            return None;
+        }
+        self.values.get(address).cloned()
+    }
+}
+
+#[async_trait]
+impl EligibilityProvider for SnapshotProvider {
+    async fn gas_spent(&self, address: &AccountAddress) -> anyhow::Result<Option<BigDecimal>> {
+        Ok(self.lookup(address, SnapshotKind::Gas))
+    }
+
+    async fn claimable_amount(&self, address: &AccountAddress) -> anyhow::Result<Option<BigDecimal>> {
+        Ok(self.lookup(address, SnapshotKind::Amount))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const A: &str = "0x00000000000000000000000000000000000000aa";
+    const B: &str = "0x00000000000000000000000000000000000000bb";
+
+    fn value(provider: &SnapshotProvider, address: &str) -> Option<String> {
+        provider.values.get(&AccountAddress::from_str(address).unwrap()).map(|v| v.to_string())
+    }
+
+    #[test]
+    fn test_from_csv() {
+        let csv = format!("address,gas\n{},0.5\n\n {} , 2 \n", A, B.to_uppercase().replace("0X", "0x"));
+        let provider = SnapshotProvider::from_csv(&csv).unwrap();
+        assert_eq!(provider.kind, SnapshotKind::Gas);
+        assert_eq!(value(&provider, A).as_deref(), Some("0.5"));
+        assert_eq!(value(&provider, B).as_deref(), Some("2"));
+
+        let provider = SnapshotProvider::from_csv(&format!("Address,Amount\n{},10\n", A)).unwrap();
+        assert_eq!(provider.kind, SnapshotKind::Amount);
+    }
+
+    #[test]
+    fn test_from_csv_invalid() {
+        assert!(SnapshotProvider::from_csv("").is_err());
+        assert!(SnapshotProvider::from_csv("address,balance\n").is_err());
+        assert!(SnapshotProvider::from_csv(&format!("address,gas\n{}\n", A)).is_err());
+        assert!(SnapshotProvider::from_csv(&format!("address,gas\n{},abc\n", A)).is_err());
+        assert!(SnapshotProvider::from_csv("address,gas\n0x1234,1\n").is_err());
+        assert!(SnapshotProvider::from_csv(&format!("address,gas\n{},1\n{},2\n", A, A)).is_err());
+    }
+
+    #[test]
+    fn test_from_json() {
+        let json = format!(r#"[{{"address":"{}","amount":"10.5"}},{{"address":"{}","amount":3}}]"#, A, B);
+        let provider = SnapshotProvider::from_json(&json).unwrap();
+        assert_eq!(provider.kind, SnapshotKind::Amount);
+        assert_eq!(value(&provider, A).as_deref(), Some("10.5"));
+        assert_eq!(value(&provider, B).as_deref(), Some("3"));
+
+        let provider = SnapshotProvider::from_json(&format!(r#"[{{"address":"{}","gas":"0.1"}}]"#, A)).unwrap();
+        assert_eq!(provider.kind, SnapshotKind::Gas);
+    }
+
+    #[test]
+    fn test_from_json_invalid() {
+        // mixed kinds
+        let json = format!(r#"[{{"address":"{}","gas":"1"}},{{"address":"{}","amount":"1"}}]"#, A, B);
+        assert!(SnapshotProvider::from_json(&json).is_err());
+        // both values, no value, unknown field
+        assert!(SnapshotProvider::from_json(&format!(r#"[{{"address":"{}","gas":"1","amount":"1"}}]"#, A)).is_err());
+        assert!(SnapshotProvider::from_json(&format!(r#"[{{"address":"{}"}}]"#, A)).is_err());
+        assert!(SnapshotProvider::from_json(&format!(r#"[{{"address":"{}","gas":"1","note":"x"}}]"#, A)).is_err());
+        // malformed values and addresses
+        assert!(SnapshotProvider::from_json(&format!(r#"[{{"address":"{}","gas":"x"}}]"#, A)).is_err());
+        assert!(SnapshotProvider::from_json(&format!(r#"[{{"address":"{}","gas":true}}]"#, A)).is_err());
+        assert!(SnapshotProvider::from_json(r#"[{"address":"0x12","gas":"1"}]"#).is_err());
+        assert!(SnapshotProvider::from_json(r#"[{"gas":"1"}]"#).is_err());
+        // duplicates
+        let json = format!(r#"[{{"address":"{}","gas":"1"}},{{"address":"{}","gas":"2"}}]"#, A, A);
+        assert!(SnapshotProvider::from_json(&json).is_err());
+    }
+}
@@ -1,22 +1,22 @@
+pub mod address;
+pub mod amount;
 pub mod server;
 pub mod config;
 pub mod route;
 pub mod db;
 pub mod watcher;
-
-use std::cell::RefCell;
-use std::sync::{Arc, Mutex};
+pub mod eligibility;
+pub mod campaign;
+pub mod tree;
+pub mod reward;
+pub mod cli;
+pub mod storage;
+
+use clap::Parser;
 use dotenvy::dotenv;
+use crate::cli::{Cli, Command};
 use crate::config::Config;
-use crate::server::AppState;
-use futures::executor::block_on;
-use futures::channel::mpsc;
-use futures::SinkExt;
-use futures::StreamExt;
-use merkle_tree_rs::standard::StandardMerkleTree;
 use rbatis::RBatis;
-use crate::db::tables::QueryAccount;
-use crate::watcher::watcher::run_watcher;
 
 pub fn init_db(db_url:String,pool_size: usize) -> RBatis {
     let rb = RBatis::new();
@@ -26,61 +26,30 @@ pub fn init_db(db_url:String,pool_size: usize) -> RBatis {
         .expect("get pool failed");
     pool.resize(pool_size);
     log::info!("postgres database init ok!");
-    return rb;
+    rb
+}
+
+async fn run(cli: Cli) -> anyhow::Result<()> {
+    let config = Config::from_env()?;
+    match cli.command.unwrap_or(Command::Run) {
+        Command::Run => cli::run::run(config, true, true).await,
+        Command::Serve => cli::run::run(config, true, false).await,
+        Command::Watch => cli::run::run(config, false, true).await,
+        Command::Sync(args) => cli::sync::run(config, args).await,
+        Command::BuildTree(args) => cli::build_tree::run(config, args).await,
+        Command::Export(args) => cli::export::run(config, args).await,
+        Command::Migrate(args) => cli::migrate::run(config, args).await,
+    }
 }
 
 #[actix_rt::main]
 async fn main() -> std::io::Result<()> {
+    let cli = Cli::parse();
     dotenv().expect("Config file not found");
     env_logger::init();
-    let config = Config::from_env();
-    let rb = init_db(config.database_url.clone(), config.db_pool_size as usize);
-    let accounts_eligible = db::get_all_queried_accounts(&rb)
-        .await.expect("get all queried accounts from db failed");
-    let tree_values = accounts_eligible.iter()
-        .map(|ae| vec![ae.address.clone(),ae.claimable_amount.clone()])
-        .collect::<Vec<_>>();
-    //because the merkle proof should be made from index 1,we add a zero account on the index 0 of tree node
-    if config.claim_start && tree_values.is_empty() {
-        let zero_account = QueryAccount::default();
-        db::save_query_account(rb.clone(),zero_account).await.expect("add zero account failed!");
-    }
-    let eligible_tree = if config.claim_start {
-        let tree: StandardMerkleTree = StandardMerkleTree::of(tree_values, &["address".to_string(), "uint256".to_string()]);
-        Some(Arc::new(Mutex::new(tree)))
-    } else {
-        None
-    };
-
-    let app_state = AppState {
-        config:config.clone(),
-        db: rb.clone(),
-        eligible_tree,
-    };
-    server::run_server(app_state).await;
-
-    let watcher_handler = run_watcher(config.clone(),rb.clone()).await;
-
-    // handle ctrl+c
-    let (stop_signal_sender, mut stop_signal_receiver) = mpsc::channel(256);
-    {
-        let stop_signal_sender = RefCell::new(stop_signal_sender.clone());
-        ctrlc::set_handler(move || {
-            let mut sender = stop_signal_sender.borrow_mut();
-            block_on(sender.send(true)).expect("Ctrl+C signal send");
-        })
-            .expect("Error setting Ctrl+C handler");
+    if let Err(e) = run(cli).await {
+        eprintln!("{:?}", e);
+        std::process::exit(1);
     }
-
-    tokio::select! {
-        Err(e) = watcher_handler => {
-            if e.is_panic() { log::error!("The one of watcher actors unexpectedly panic:{}", e) }
-            log::error!("Watchers actors aren't supposed to finish any of their execution")
-        },
-        _ = async { stop_signal_receiver.next().await } => {
-            log::warn!("Stop signal received, shutting down");
-        }
-    };
-
     Ok(())
 }
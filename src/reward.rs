@@ -0,0 +1,282 @@
+use std::fs;
+use std::str::FromStr;
+use anyhow::format_err;
+use bigdecimal::{BigDecimal, One, Zero};
+use num::BigInt;
+use serde::{Deserialize, Serialize};
+use crate::amount::{Rounding, TokenAmount};
+use crate::config::Config;
+
+/// A bracket of the reward formula. The gas spent from `from_gas` up to the next bracket
+/// earns one token per `gas_per_token`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Tier {
+    pub from_gas: BigDecimal,
+    pub gas_per_token: BigDecimal,
+}
+
+/// Multiplies the allocation of accounts which spent at least `min_gas`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Bonus {
+    pub min_gas: BigDecimal,
+    pub multiplier: BigDecimal,
+}
+
+/// Declarative definition of how many whole tokens an account earns for the gas it spent.
+///
+/// The allocation is zero unless the gas is above `min_gas`. Otherwise it is the sum over
+/// `tiers`, multiplied by the largest `bonuses` multiplier reached, then raised to
+/// `min_allocation` and lowered to `max_allocation`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RewardFormula {
+    #[serde(default)]
+    pub min_gas: BigDecimal,
+    pub tiers: Vec<Tier>,
+    #[serde(default)]
+    pub bonuses: Vec<Bonus>,
+    #[serde(default)]
+    pub min_allocation: Option<BigDecimal>,
+    #[serde(default)]
+    pub max_allocation: Option<BigDecimal>,
+}
+
+impl RewardFormula {
+    /// The formula of `TOKENS_NUMBER_PER_GAS` and `ELIGIBLE_MIN_GAS`, a single tier and no other rule.
+    pub fn legacy(tokens_number_per_gas: &str, eligible_min_gas: &str) -> Self {
+        let gas_per_token = BigDecimal::from_str(tokens_number_per_gas).unwrap_or_default();
+        let tiers = if gas_per_token.is_zero() {
+            vec![]
+        } else {
+            vec![Tier { from_gas: BigDecimal::zero(), gas_per_token }]
+        };
+        Self {
+            min_gas: BigDecimal::from_str(eligible_min_gas).unwrap_or_default(),
+            tiers,
+            bonuses: vec![],
+            min_allocation: None,
+            max_allocation: None,
+        }
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.min_gas < BigDecimal::zero() {
+            return Err(format_err!("reward formula min_gas is negative"));
+        }
+        for (i, tier) in self.tiers.iter().enumerate() {
+            if tier.gas_per_token <= BigDecimal::zero() {
+                return Err(format_err!("reward formula tier {} gas_per_token must be positive", i));
+            }
+            if i == 0 && tier.from_gas != BigDecimal::zero() {
+                return Err(format_err!("reward formula first tier must start from gas 0"));
+            }
+            if i > 0 && tier.from_gas <= self.tiers[i - 1].from_gas {
+                return Err(format_err!("reward formula tiers must be sorted by increasing from_gas"));
+            }
+        }
+        for (i, bonus) in self.bonuses.iter().enumerate() {
+            if bonus.multiplier <= BigDecimal::zero() {
+                return Err(format_err!("reward formula bonus {} multiplier must be positive", i));
+            }
+        }
+        if let (Some(min), Some(max)) = (&self.min_allocation, &self.max_allocation) {
+            if min > max {
+                return Err(format_err!("reward formula min_allocation is above max_allocation"));
+            }
+        }
+        Ok(())
+    }
+
+    /// The whole tokens earned by `gas`, before scaling to base units.
+    pub fn evaluate(&self, gas: &BigDecimal) -> BigDecimal {
+        if gas <= &self.min_gas {
+            return BigDecimal::zero();
+        }
+        let mut amount = BigDecimal::zero();
+        for (i, tier) in self.tiers.iter().enumerate() {
+            if gas <= &tier.from_gas {
+                break;
+            }
+            let upper = match self.tiers.get(i + 1) {
+                Some(next) if &next.from_gas < gas => &next.from_gas,
+                _ => gas,
+            };
+            amount += (upper - &tier.from_gas) / &tier.gas_per_token;
+        }
+        let multiplier = self.bonuses.iter()
+            .filter(|b| gas >= &b.min_gas)
+            .map(|b| &b.multiplier)
+            .max()
+            .cloned()
+            .unwrap_or_else(BigDecimal::one);
+        amount *= multiplier;
+        if let Some(min_allocation) = &self.min_allocation {
+            if &amount < min_allocation {
+                amount = min_allocation.clone();
+            }
+        }
+        if let Some(max_allocation) = &self.max_allocation {
+            if &amount > max_allocation {
+                amount = max_allocation.clone();
+            }
+        }
+        amount
+    }
+}
+
+/// Turns gas into claimable base units, for `get_eligible` and for building the eligible tree.
+#[derive(Clone, Debug)]
+pub struct RewardEngine {
+    pub formula: RewardFormula,
+    pub token_decimal: u32,
+    pub rounding: Rounding,
+    /// The most base units the eligible tree may allocate in total.
+    pub total_budget: Option<BigInt>,
+}
+
+impl RewardEngine {
+    /// Loads the formula from `REWARD_FORMULA` (JSON), else from the `REWARD_FORMULA_PATH` JSON
+    /// file, else from `TOKENS_NUMBER_PER_GAS` and `ELIGIBLE_MIN_GAS`.
+    pub fn from_config(config: &Config) -> anyhow::Result<Self> {
+        let formula = if !config.reward_formula.is_empty() {
+            serde_json::from_str(&config.reward_formula)
+                .map_err(|e| format_err!("invalid REWARD_FORMULA,{:?}", e))?
+        } else if !config.reward_formula_path.is_empty() {
+            let content = fs::read_to_string(&config.reward_formula_path)
+                .map_err(|e| format_err!("read reward formula {} failed,{:?}", config.reward_formula_path, e))?;
+            serde_json::from_str(&content)
+                .map_err(|e| format_err!("invalid reward formula {},{:?}", config.reward_formula_path, e))?
+        } else {
+            RewardFormula::legacy(&config.tokens_number_per_gas, &config.eligible_min_gas)
+        };
+        formula.validate()?;
+        let mut engine = Self {
+            formula,
+            token_decimal: config.token_decimal,
+            rounding: config.amount_rounding,
+            total_budget: None,
+        };
+        if !config.total_budget.is_empty() {
+            engine.set_total_budget(&config.total_budget)?;
+        }
+        Ok(engine)
+    }
+
+    /// Sets the total budget from an amount of whole tokens.
+    pub fn set_total_budget(&mut self, budget: &str) -> anyhow::Result<()> {
+        let budget = BigDecimal::from_str(budget)
+            .map_err(|e| format_err!("invalid total budget {},{:?}", budget, e))?;
+        if budget < BigDecimal::zero() {
+            return Err(format_err!("total budget is negative"));
+        }
+        self.total_budget = Some(TokenAmount::from_human(&budget, self.token_decimal, Rounding::Down).base_units);
+        Ok(())
+    }
+
+    pub fn claimable_amount(&self, gas: &BigDecimal) -> TokenAmount {
+        self.scale(&self.formula.evaluate(gas))
+    }
+
+    /// Scales a pre-computed amount of whole tokens to base units.
+    pub fn scale(&self, amount: &BigDecimal) -> TokenAmount {
+        TokenAmount::from_human(amount, self.token_decimal, self.rounding)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn dec(s: &str) -> BigDecimal {
+        BigDecimal::from_str(s).unwrap()
+    }
+
+    fn formula(json: &str) -> RewardFormula {
+        let formula: RewardFormula = serde_json::from_str(json).unwrap();
+        formula.validate().unwrap();
+        formula
+    }
+
+    #[test]
+    fn test_min_gas() {
+        let f = formula(r#"{"min_gas": "0.01", "tiers": [{"from_gas": "0", "gas_per_token": "0.001"}]}"#);
+        assert_eq!(f.evaluate(&dec("0.01")), dec("0"));
+        assert_eq!(f.evaluate(&dec("0.02")), dec("20"));
+    }
+
+    #[test]
+    fn test_tiers() {
+        let f = formula(r#"{"tiers": [
+            {"from_gas": "0", "gas_per_token": "0.001"},
+            {"from_gas": "0.1", "gas_per_token": "0.01"},
+            {"from_gas": "1", "gas_per_token": "0.1"}
+        ]}"#);
+        assert_eq!(f.evaluate(&dec("0.05")), dec("50"));
+        assert_eq!(f.evaluate(&dec("0.1")), dec("100"));
+        assert_eq!(f.evaluate(&dec("0.5")), dec("140"));
+        assert_eq!(f.evaluate(&dec("3")), dec("210"));
+    }
+
+    #[test]
+    fn test_bonus_multiplier() {
+        let f = formula(r#"{"tiers": [{"from_gas": "0", "gas_per_token": "0.01"}], "bonuses": [
+            {"min_gas": "1", "multiplier": "1.5"},
+            {"min_gas": "2", "multiplier": "2"}
+        ]}"#);
+        assert_eq!(f.evaluate(&dec("0.5")), dec("50"));
+        assert_eq!(f.evaluate(&dec("1")), dec("150"));
+        assert_eq!(f.evaluate(&dec("2")), dec("400"));
+    }
+
+    #[test]
+    fn test_min_allocation() {
+        let f = formula(r#"{"min_gas": "0.01", "tiers": [{"from_gas": "0", "gas_per_token": "0.01"}],
+            "min_allocation": "5"}"#);
+        assert_eq!(f.evaluate(&dec("0.005")), dec("0"));
+        assert_eq!(f.evaluate(&dec("0.02")), dec("5"));
+        assert_eq!(f.evaluate(&dec("0.1")), dec("10"));
+    }
+
+    #[test]
+    fn test_max_allocation() {
+        let f = formula(r#"{"tiers": [{"from_gas": "0", "gas_per_token": "0.01"}],
+            "bonuses": [{"min_gas": "0", "multiplier": "2"}], "max_allocation": "100"}"#);
+        assert_eq!(f.evaluate(&dec("0.4")), dec("80"));
+        assert_eq!(f.evaluate(&dec("10")), dec("100"));
+    }
+
+    #[test]
+    fn test_legacy_formula() {
+        let f = RewardFormula::legacy("0.001", "0.01");
+        assert_eq!(f.evaluate(&dec("0.01")), dec("0"));
+        assert_eq!(f.evaluate(&dec("0.0125")), dec("12.5"));
+        assert_eq!(RewardFormula::legacy("", "").evaluate(&dec("1")), dec("0"));
+    }
+
+    #[test]
+    fn test_invalid_formula() {
+        for json in [
+            r#"{"tiers": [{"from_gas": "0", "gas_per_token": "0"}]}"#,
+            r#"{"tiers": [{"from_gas": "1", "gas_per_token": "1"}]}"#,
+            r#"{"tiers": [{"from_gas": "0", "gas_per_token": "1"}, {"from_gas": "0", "gas_per_token": "1"}]}"#,
+            r#"{"tiers": [], "bonuses": [{"min_gas": "0", "multiplier": "-1"}]}"#,
+            r#"{"tiers": [], "min_allocation": "10", "max_allocation": "1"}"#,
+        ] {
+            let formula: RewardFormula = serde_json::from_str(json).unwrap();
+            assert!(formula.validate().is_err(), "{json} should be rejected");
+        }
+    }
+
+    #[test]
+    fn test_unknown_field() {
+        for json in [
+            r#"{"tiers": [], "max_alocation": "100"}"#,
+            r#"{"tiers": [{"from_gas": "0", "gas_per_token": "1", "gas_per_tokens": "2"}]}"#,
+            r#"{"tiers": [], "bonuses": [{"min_gas": "0", "multiplier": "2", "min_gaz": "1"}]}"#,
+        ] {
+            assert!(serde_json::from_str::<RewardFormula>(json).is_err(), "{json} should be rejected");
+        }
+    }
+}
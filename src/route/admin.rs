@@ -0,0 +1,113 @@
+use actix_web::{HttpRequest, HttpResponse, web};
+use actix_web::http::header;
+use qstring::QString;
+use serde::{Deserialize, Serialize};
+use subtle::ConstantTimeEq;
+use crate::db;
+use crate::route::BackendResponse;
+use crate::route::err::BackendError;
+use crate::server::AppState;
+use crate::watcher::claim_check::AnomalyKind;
+
+const DEFAULT_PAGE_SIZE: i64 = 100;
+const MAX_PAGE_SIZE: i64 = 1000;
+
+/// An indexed claim which doesn't match the eligible tree, amounts in the token's base units.
+#[derive(Clone,Debug,Serialize,Deserialize)]
+pub struct ClaimAnomalyResp {
+    pub tx_hash: String,
+    pub log_index: i64,
+    pub kind: String,
+    pub block_number: i64,
+    pub address: String,
+    pub claimed_amount: String,
+    pub expected_amount: Option<String>,
+    pub snapshot_version: i64,
+    pub detected_at: i64,
+}
+
+/// Whether the request carries `ADMIN_TOKEN` as its bearer token, compared in constant time. Always false when
+/// no token is set.
+fn is_admin(data: &AppState, req: &HttpRequest) -> bool {
+    if data.config.admin_token.is_empty() {
+        return false;
+    }
+    req.headers().get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|token| bool::from(token.as_bytes().ct_eq(data.config.admin_token.as_bytes())))
+}
+
+fn invalid_parameter(error: &str) -> HttpResponse {
+    let resp = BackendResponse {
+        code: BackendError::InvalidParameters,
+        error: Some(error.to_string()),
+        data: None::<()>
+    };
+    HttpResponse::Ok().json(resp)
+}
+
+/// Lists the claim anomalies, latest first. Optional query parameters: `kind`, `limit` and `offset`.
+pub async fn get_claim_anomalies(data: web::Data<AppState>, req: HttpRequest)
+                                 -> actix_web::Result<HttpResponse> {
+    if !is_admin(&data, &req) {
+        let resp = BackendResponse {
+            code: BackendError::Unauthorized,
+            error: Some("unauthorized".to_string()),
+            data: None::<()>
+        };
+        return Ok(HttpResponse::Unauthorized().json(resp));
+    }
+
+    let qs = QString::from(req.query_string());
+    let kind = qs.get("kind");
+    let known_kinds = [AnomalyKind::UnknownAddress, AnomalyKind::WrongAmount, AnomalyKind::DoubleClaim];
+    if let Some(kind) = kind {
+        if !known_kinds.iter().any(|k| k.as_ref() == kind) {
+            return Ok(invalid_parameter("invalid kind"));
+        }
+    }
+    let limit = match qs.get("limit").map(|l| l.parse::<i64>()) {
+        None => DEFAULT_PAGE_SIZE,
+        Some(Ok(limit)) if (1..=MAX_PAGE_SIZE).contains(&limit) => limit,
+        Some(_) => return Ok(invalid_parameter("invalid limit")),
+    };
+    let offset = match qs.get("offset").map(|o| o.parse::<i64>()) {
+        None => 0,
+        Some(Ok(offset)) if offset >= 0 => offset,
+        Some(_) => return Ok(invalid_parameter("invalid offset")),
+    };
+
+    match db::get_claim_anomalies(&data.db, kind, limit, offset).await {
+        Ok(anomalies) => {
+            let anomalies = anomalies.into_iter()
+                .map(|a| ClaimAnomalyResp {
+                    tx_hash: a.tx_hash,
+                    log_index: a.log_index,
+                    kind: a.kind,
+                    block_number: a.block_number,
+                    address: a.address,
+                    claimed_amount: a.claimed_amount.0.to_string(),
+                    expected_amount: a.expected_amount.map(|e| e.0.to_string()),
+                    snapshot_version: a.snapshot_version,
+                    detected_at: a.detected_at,
+                })
+                .collect::<Vec<_>>();
+            let resp = BackendResponse {
+                code: BackendError::Ok,
+                error: None,
+                data: Some(anomalies)
+            };
+            Ok(HttpResponse::Ok().json(resp))
+        },
+        Err(e) => {
+            log::warn!("get_claim_anomalies failed,{e}");
+            let resp = BackendResponse {
+                code: BackendError::InternalErr,
+                error: Some("get_claim_anomalies failed".to_owned()),
+                data: None::<()>
+            };
+            Ok(HttpResponse::Ok().json(resp))
+        }
+    }
+}
@@ -1,109 +1,179 @@
-use std::collections::HashMap;
 use std::str::FromStr;
 use std::time::{SystemTime, UNIX_EPOCH};
 use actix_web::{HttpRequest, HttpResponse, web};
-use bigdecimal::{BigDecimal, Zero};
-use num::BigInt;
-use num::bigint::ToBigInt;
+use bigdecimal::BigDecimal;
 use qstring::QString;
 use rbatis::rbdc::decimal::Decimal;
+use crate::address::AccountAddress;
+use crate::amount::TokenAmount;
+use crate::campaign::CampaignPhase;
 use crate::server::AppState;
 use serde::{Serialize, Deserialize};
 use crate::db;
 use crate::db::tables::QueryAccount;
-use crate::route::BackendResponse;
+use crate::eligibility::EligibilityProvider;
+use crate::reward::RewardEngine;
+use crate::route::{BackendResponse, invalid_address};
 use crate::route::err::BackendError;
 
-#[derive(Clone,Debug,Serialize,Deserialize)]
-pub struct EligibleResult {
-    pub count: u32,
-    pub gas: HashMap<String,String>,
-}
-#[derive(Clone,Debug,Serialize,Deserialize)]
-pub struct OrbiterEligibleResp {
-    pub code: u32,
-    pub msg: String,
-    pub result: EligibleResult,
-}
-
 #[derive(Clone,Debug,Serialize,Deserialize)]
 pub struct EligibleResp {
     pub eth_gas_cost: String,
+    /// In whole tokens.
     pub claimable_amount: String,
+    /// In the token's base units, as it appears in the eligible tree.
+    pub claimable_amount_base_units: String,
 }
 
 pub async fn get_eligible(data: web::Data<AppState>, req: HttpRequest)
                           -> actix_web::Result<HttpResponse> {
-    if data.config.claim_start {
+    if data.campaign.current() != CampaignPhase::Registration {
         return Ok(HttpResponse::BadRequest().finish());
     }
     let query_str = req.query_string();
     let qs = QString::from(query_str);
-    let address = qs.get("address").unwrap_or("0");
-    let now = SystemTime::now();
-    let since_epoch = now.duration_since(UNIX_EPOCH).expect("Time went backwards");
-    let timestamp = since_epoch.as_secs();
-    let base_url = "https://openapi.orbiter.finance/mainnet/v1/gas";
-    let url = format!("{}?address={}", base_url, address);
-    match reqwest::Client::new().get(url).send().await {
-        Ok(resp) => {
-            if resp.status().is_success() {
-                let ret = resp.text().await.unwrap();
-                let eligible_ret: OrbiterEligibleResp = serde_json::from_str(&ret).unwrap();
-                let gas_eth_cost = eligible_ret.result.gas.get("ETH");
-                if gas_eth_cost.is_none() {
-                    let resp = BackendResponse {
-                        code: BackendError::Ok,
-                        error: None,
-                        data: Some(EligibleResp {
-                            eth_gas_cost: "0".to_string(),
-                            claimable_amount: "0".to_string(),
-                        })
-                    };
-                    return Ok(HttpResponse::Ok().json(resp));
-                }
-                let tokens_number_per_gas = BigDecimal::from_str(&data.config.tokens_number_per_gas).unwrap_or_default();
-                let gas_eth_cost = BigDecimal::from_str(gas_eth_cost.unwrap()).unwrap_or_default();
-                let eligible_min_gas = BigDecimal::from_str(&data.config.eligible_min_gas).unwrap_or_default();
-                let claimable_amount = if gas_eth_cost.gt(&eligible_min_gas) && !tokens_number_per_gas.is_zero() {
-                    (gas_eth_cost.clone() / tokens_number_per_gas).to_bigint().unwrap()
-                } else {
-                    BigInt::from(0)
-                };
-
-                if let Err(e) = db::save_query_account(data.db.clone(), QueryAccount {
-                    address: address.to_string(),
-                    claimable_amount: Decimal::from_str(&claimable_amount.to_string()).unwrap(),
-                    query_time: timestamp as i64,
-                }).await {
-                    log::warn!("save_query_account failed ,{e}")
-                };
-                let resp = BackendResponse {
-                    code: BackendError::Ok,
-                    error: None,
-                    data: Some(EligibleResp {
-                        eth_gas_cost: gas_eth_cost.to_string(),
-                        claimable_amount: claimable_amount.to_string(),
-                    })
-                };
-                Ok(HttpResponse::Ok().json(resp))
-            } else {
-                let resp = BackendResponse {
-                    code: BackendError::InternalErr,
-                    error: Some("Orbiter api return failed".to_owned()),
-                    data: None::<()>
-                };
-                Ok(HttpResponse::Ok().json(resp))
-            }
-
+    let address = match AccountAddress::from_str(qs.get("address").unwrap_or_default()) {
+        Ok(address) => address,
+        Err(_) => return Ok(invalid_address()),
+    };
+    match check_eligibility(&data, &address).await {
+        Ok(eligibility) => {
+            let resp = BackendResponse {
+                code: BackendError::Ok,
+                error: None,
+                data: Some(eligibility)
+            };
+            Ok(HttpResponse::Ok().json(resp))
         },
-        Err(_e) => {
+        Err(e) => {
+            log::warn!("query eligibility of {address} failed,{e}");
             let resp = BackendResponse {
                 code: BackendError::InternalErr,
-                error: Some("Orbiter api connected failed".to_owned()),
+                error: Some("eligibility provider query failed".to_owned()),
                 data: None::<()>
             };
             Ok(HttpResponse::Ok().json(resp))
         }
     }
 }
+
+/// Queries the eligibility of `address` and records the query. Accounts the eligibility provider
+/// knows nothing about are not recorded and get nothing.
+pub(crate) async fn check_eligibility(data: &AppState, address: &AccountAddress) -> anyhow::Result<EligibleResp> {
+    let now = SystemTime::now();
+    let since_epoch = now.duration_since(UNIX_EPOCH).expect("Time went backwards");
+    let timestamp = since_epoch.as_secs();
+    let eligibility = match query_eligible(data.eligibility_provider.as_ref(), &data.reward_engine, address).await? {
+        Some(eligible) => eligible,
+        None => return Ok(EligibleResp {
+            eth_gas_cost: "0".to_string(),
+            claimable_amount: "0".to_string(),
+            claimable_amount_base_units: "0".to_string(),
+        }),
+    };
+
+    let QueriedEligibility { gas_eth_cost, claimable_amount, from_gas } = eligibility;
+    let eth_gas_cost = if from_gas {
+        Some(Decimal::from_str(&gas_eth_cost.to_string()).unwrap())
+    } else {
+        None
+    };
+    if let Err(e) = db::save_query_account(data.db.clone(), QueryAccount {
+        address: address.to_string(),
+        claimable_amount: Decimal::from_str(&claimable_amount.base_units.to_string()).unwrap(),
+        query_time: timestamp as i64,
+        eth_gas_cost,
+        base_units: true,
+    }).await {
+        log::warn!("save_query_account failed ,{e}")
+    };
+    Ok(EligibleResp {
+        eth_gas_cost: gas_eth_cost.to_string(),
+        claimable_amount: claimable_amount.human.to_string(),
+        claimable_amount_base_units: claimable_amount.base_units.to_string(),
+    })
+}
+
+/// The answer of the eligibility provider for an account.
+struct QueriedEligibility {
+    gas_eth_cost: BigDecimal,
+    claimable_amount: TokenAmount,
+    /// Whether the claimable amount was computed from the gas, rather than pre-computed.
+    from_gas: bool,
+}
+
+/// Returns the gas spent and the claimable amount of `address`, or `None` if the
+/// eligibility provider knows nothing about it.
+async fn query_eligible(provider: &dyn EligibilityProvider, engine: &RewardEngine, address: &AccountAddress)
+    -> anyhow::Result<Option<QueriedEligibility>> {
+    let gas_eth_cost = provider.gas_spent(address).await?;
+    if let Some(amount) = provider.claimable_amount(address).await? {
+        return Ok(Some(QueriedEligibility {
+            gas_eth_cost: gas_eth_cost.unwrap_or_default(),
+            claimable_amount: engine.scale(&amount),
+            from_gas: false,
+        }));
+    }
+    Ok(gas_eth_cost.map(|gas_eth_cost| QueriedEligibility {
+        claimable_amount: engine.claimable_amount(&gas_eth_cost),
+        gas_eth_cost,
+        from_gas: true,
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::amount::Rounding;
+    use crate::eligibility::fake::FakeProvider;
+    use crate::reward::RewardFormula;
+
+    fn engine() -> RewardEngine {
+        RewardEngine {
+            formula: RewardFormula::legacy("10", "0.01"),
+            token_decimal: 2,
+            rounding: Rounding::Down,
+            total_budget: None,
+        }
+    }
+
+    fn address(n: u64) -> AccountAddress {
+        AccountAddress::from(web3::types::H160::from_low_u64_be(n))
+    }
+
+    fn dec(s: &str) -> BigDecimal {
+        BigDecimal::from_str(s).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_query_eligible_from_gas() {
+        let mut provider = FakeProvider::default();
+        provider.gas.insert(address(1), dec("0.5"));
+        let eligibility = query_eligible(&provider, &engine(), &address(1)).await.unwrap().unwrap();
+        assert!(eligibility.from_gas);
+        assert_eq!(eligibility.gas_eth_cost, dec("0.5"));
+        assert_eq!(eligibility.claimable_amount.base_units.to_string(), "5");
+    }
+
+    #[tokio::test]
+    async fn test_query_eligible_precomputed_amount() {
+        let mut provider = FakeProvider::default();
+        provider.gas.insert(address(1), dec("0.5"));
+        provider.amounts.insert(address(1), dec("3"));
+        provider.amounts.insert(address(2), dec("1.5"));
+        let eligibility = query_eligible(&provider, &engine(), &address(1)).await.unwrap().unwrap();
+        assert!(!eligibility.from_gas);
+        assert_eq!(eligibility.claimable_amount.base_units.to_string(), "300");
+        let eligibility = query_eligible(&provider, &engine(), &address(2)).await.unwrap().unwrap();
+        assert_eq!(eligibility.gas_eth_cost, BigDecimal::default());
+        assert_eq!(eligibility.claimable_amount.base_units.to_string(), "150");
+    }
+
+    #[tokio::test]
+    async fn test_query_eligible_unknown_or_unavailable() {
+        let provider = FakeProvider::default();
+        assert!(query_eligible(&provider, &engine(), &address(1)).await.unwrap().is_none());
+        let provider = FakeProvider { unavailable: true, ..Default::default() };
+        assert!(query_eligible(&provider, &engine(), &address(1)).await.is_err());
+    }
+}
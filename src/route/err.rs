@@ -1,11 +1,17 @@
+use actix_web::{HttpResponse, ResponseError};
+use actix_web::http::StatusCode;
 use serde::Serialize;
-use std::fmt::{Debug, Formatter};
+use std::fmt::{Debug, Display, Formatter};
+use crate::route::BackendResponse;
 
 #[derive(Copy, Clone, Serialize)]
 pub enum BackendError {
     Ok = 0,
     DbErr = 100,
     InvalidParameters = 201,
+    Unauthorized = 401,
+    NotFound = 404,
+    WrongPhase = 409,
     InternalErr = 500,
 }
 
@@ -15,13 +21,116 @@ impl Debug for BackendError {
     }
 }
 
+impl Display for BackendError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_ref())
+    }
+}
+
 impl AsRef<str> for BackendError {
     fn as_ref(&self) -> &'static str {
         match self {
             BackendError::Ok => "Ok",
             BackendError::DbErr => "Db error",
             BackendError::InvalidParameters => "Invalid request parameters",
+            BackendError::Unauthorized => "Unauthorized",
+            BackendError::NotFound => "Not found",
+            BackendError::WrongPhase => "Not available in the current campaign phase",
             BackendError::InternalErr => "Server internal error",
         }
     }
 }
+
+impl ResponseError for BackendError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            BackendError::Ok => StatusCode::OK,
+            BackendError::InvalidParameters => StatusCode::BAD_REQUEST,
+            BackendError::Unauthorized => StatusCode::UNAUTHORIZED,
+            BackendError::NotFound => StatusCode::NOT_FOUND,
+            BackendError::WrongPhase => StatusCode::CONFLICT,
+            BackendError::DbErr | BackendError::InternalErr => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        ApiError::from(*self).error_response()
+    }
+}
+
+/// A [`BackendError`] with a message for the client, answered with the status of its code.
+#[derive(Debug)]
+pub struct ApiError {
+    pub code: BackendError,
+    pub message: String,
+}
+
+impl ApiError {
+    pub fn new(code: BackendError, message: impl Into<String>) -> Self {
+        Self { code, message: message.into() }
+    }
+}
+
+impl From<BackendError> for ApiError {
+    fn from(code: BackendError) -> Self {
+        Self::new(code, code.as_ref())
+    }
+}
+
+impl Display for ApiError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        self.code.status_code()
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let resp = BackendResponse {
+            code: self.code,
+            error: Some(self.message.clone()),
+            data: None::<()>
+        };
+        HttpResponse::build(self.status_code()).json(resp)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use actix_web::body::to_bytes;
+    use super::*;
+
+    #[test]
+    fn test_status_codes() {
+        let statuses = [
+            (BackendError::Ok, StatusCode::OK),
+            (BackendError::DbErr, StatusCode::INTERNAL_SERVER_ERROR),
+            (BackendError::InvalidParameters, StatusCode::BAD_REQUEST),
+            (BackendError::Unauthorized, StatusCode::UNAUTHORIZED),
+            (BackendError::NotFound, StatusCode::NOT_FOUND),
+            (BackendError::WrongPhase, StatusCode::CONFLICT),
+            (BackendError::InternalErr, StatusCode::INTERNAL_SERVER_ERROR),
+        ];
+        for (code, status) in statuses {
+            assert_eq!(code.status_code(), status, "{:?}", code);
+            assert_eq!(ApiError::from(code).status_code(), status, "{:?}", code);
+            assert_eq!(code.error_response().status(), status, "{:?}", code);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_error_body() {
+        let resp = ApiError::new(BackendError::NotFound, "account not found").error_response();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let body = to_bytes(resp.into_body()).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body, serde_json::json!({"code": "NotFound", "error": "account not found", "data": null}));
+
+        let resp = BackendError::WrongPhase.error_response();
+        let body: serde_json::Value = serde_json::from_slice(&to_bytes(resp.into_body()).await.unwrap()).unwrap();
+        assert_eq!(body["error"], "Not available in the current campaign phase");
+    }
+}
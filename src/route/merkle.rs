@@ -1,8 +1,10 @@
 use actix_web::{HttpRequest, HttpResponse, web};
-use merkle_tree_rs::standard::LeafType;
+use std::str::FromStr;
+use crate::address::AccountAddress;
 use qstring::QString;
 use serde::{Deserialize, Serialize};
-use crate::route::BackendResponse;
+use crate::campaign::CampaignPhase;
+use crate::route::{BackendResponse, invalid_address};
 use crate::route::err::BackendError;
 use crate::server::AppState;
 
@@ -12,32 +14,46 @@ pub struct EligibleProofResp {
     pub amount: String,
     pub proof: Vec<String>,
 }
+#[derive(Clone,Debug,Serialize,Deserialize)]
+pub struct EligibleTreeRootResp {
+    pub root: String,
+    /// sum of the leaf amounts, in token base units
+    pub total_amount: String,
+}
 pub async fn get_eligible_tree_root(data: web::Data<AppState>, _req: HttpRequest)
                                     -> actix_web::Result<HttpResponse> {
-    if !data.config.claim_start {
+    if !data.campaign.current().is_tree_frozen() {
         let resp = BackendResponse {
             code: BackendError::InvalidParameters,
-            error: Some("claim not start".to_string()),
+            error: Some("snapshot not frozen".to_string()),
             data: None::<()>
         };
         return Ok(HttpResponse::Ok().json(resp));
     }
 
-    let root = data.eligible_tree.as_ref().unwrap().lock().unwrap().root();
+    let tree = match data.eligible_tree().await {
+        Ok(tree) => tree,
+        Err(e) => return Ok(tree_unavailable(e)),
+    };
     let resp = BackendResponse {
         code: BackendError::Ok,
         error: None,
-        data: Some(root)
+        data: Some(EligibleTreeRootResp {
+            root: tree.root().to_string(),
+            total_amount: tree.total_amount().to_string(),
+        })
     };
     Ok(HttpResponse::Ok().json(resp))
 }
 
 pub async fn get_eligible_proof(data: web::Data<AppState>, req: HttpRequest)
                                     -> actix_web::Result<HttpResponse> {
-    if !data.config.claim_start {
+    let phase = data.campaign.current();
+    if phase != CampaignPhase::Claiming {
+        let error = if phase == CampaignPhase::Ended { "claim ended" } else { "claim not start" };
         let resp = BackendResponse {
             code: BackendError::InvalidParameters,
-            error: Some("claim not start".to_string()),
+            error: Some(error.to_string()),
             data: None::<()>
         };
         return Ok(HttpResponse::Ok().json(resp));
@@ -45,24 +61,25 @@ pub async fn get_eligible_proof(data: web::Data<AppState>, req: HttpRequest)
 
     let query_str = req.query_string();
     let qs = QString::from(query_str);
-    let address = qs.get("address").unwrap_or("0");
-    let tree = data.eligible_tree.as_ref().unwrap().lock().unwrap();
-    for (i,v) in (*tree).clone().enumerate() {
-        // println!("Value : {:?} {:?}", i,v);
-        if v[0] == address {
-            let proof = tree.get_proof(LeafType::Number(i));
-            println!("Proof : {:?}", proof);
-            let resp = BackendResponse {
-                code: BackendError::Ok,
-                error: None,
-                data: Some(EligibleProofResp {
-                    address: address.to_string(),
-                    amount: v[1].to_string(),
-                    proof,
-                })
-            };
-            return Ok(HttpResponse::Ok().json(resp));
-        }
+    let address = match AccountAddress::from_str(qs.get("address").unwrap_or_default()) {
+        Ok(address) => address,
+        Err(_) => return Ok(invalid_address()),
+    };
+    let tree = match data.eligible_tree().await {
+        Ok(tree) => tree,
+        Err(e) => return Ok(tree_unavailable(e)),
+    };
+    if let Some((_, leaf)) = tree.get(address.as_h160()) {
+        let resp = BackendResponse {
+            code: BackendError::Ok,
+            error: None,
+            data: Some(EligibleProofResp {
+                address: address.to_string(),
+                amount: leaf.amount.clone(),
+                proof: leaf.proof.clone(),
+            })
+        };
+        return Ok(HttpResponse::Ok().json(resp));
     }
 
     //not found
@@ -72,4 +89,14 @@ pub async fn get_eligible_proof(data: web::Data<AppState>, req: HttpRequest)
         data: None::<()>
     };
     Ok(HttpResponse::Ok().json(resp))
-}
\ No newline at end of file
+}
+
+fn tree_unavailable(e: anyhow::Error) -> HttpResponse {
+    log::warn!("load eligible tree failed,{e}");
+    let resp = BackendResponse {
+        code: BackendError::InternalErr,
+        error: Some("eligible tree unavailable".to_string()),
+        data: None::<()>
+    };
+    HttpResponse::Ok().json(resp)
+}
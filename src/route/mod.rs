@@ -1,10 +1,13 @@
+use actix_web::HttpResponse;
 use serde::Serialize;
 use crate::route::err::BackendError;
 
+pub mod admin;
 pub mod eligible;
 pub mod err;
 pub mod stat;
 pub mod merkle;
+pub mod v1;
 
 #[derive(Debug, Serialize, Clone)]
 pub struct BackendResponse<T: Clone + Serialize> {
@@ -12,3 +15,12 @@ pub struct BackendResponse<T: Clone + Serialize> {
     pub error: Option<String>,
     pub data: Option<T>
 }
+
+pub(crate) fn invalid_address() -> HttpResponse {
+    let resp = BackendResponse {
+        code: BackendError::InvalidParameters,
+        error: Some("invalid address".to_string()),
+        data: None::<()>
+    };
+    HttpResponse::Ok().json(resp)
+}
@@ -1,12 +1,37 @@
 use std::str::FromStr;
 use actix_web::{HttpRequest, HttpResponse, web};
 use bigdecimal::BigDecimal;
-use num::BigUint;
+use serde::{Serialize, Deserialize};
 use crate::db;
+use crate::db::tables::HolderStats;
 use crate::route::BackendResponse;
 use crate::route::err::BackendError;
 use crate::server::AppState;
 
+/// Claimers by how much of their claimed tokens they sent away after claiming, amounts in the token's base units.
+#[derive(Clone,Debug,Serialize,Deserialize)]
+pub struct HolderStatsResp {
+    pub claimers: i64,
+    pub retained: i64,
+    pub partially_sold: i64,
+    pub sold: i64,
+    pub claimed_amount: String,
+    pub retained_amount: String,
+}
+
+impl From<HolderStats> for HolderStatsResp {
+    fn from(stats: HolderStats) -> Self {
+        Self {
+            claimers: stats.claimers,
+            retained: stats.retained,
+            partially_sold: stats.partially_sold,
+            sold: stats.sold,
+            claimed_amount: stats.claimed_amount.0.to_string(),
+            retained_amount: stats.retained_amount.0.to_string(),
+        }
+    }
+}
+
 pub async fn get_queried_addresses_number(data: web::Data<AppState>, _req: HttpRequest)
                           -> actix_web::Result<HttpResponse> {
     match db::db_get_queried_addresses_number(&data.db).await {
@@ -75,4 +100,27 @@ pub async fn get_total_claimed_amount(data: web::Data<AppState>, _req: HttpReque
             Ok(HttpResponse::Ok().json(resp))
         }
     }
+}
+
+pub async fn get_holder_stats(data: web::Data<AppState>, _req: HttpRequest)
+                              -> actix_web::Result<HttpResponse> {
+    match db::db_get_holder_stats(&data.db, data.config.holder_stats_window_blocks).await {
+        Ok(stats) => {
+            let resp = BackendResponse {
+                code: BackendError::Ok,
+                error: None,
+                data: Some(HolderStatsResp::from(stats))
+            };
+            Ok(HttpResponse::Ok().json(resp))
+        },
+        Err(e) => {
+            log::warn!("get_holder_stats failed,{e}");
+            let resp = BackendResponse {
+                code: BackendError::InternalErr,
+                error: Some("get_holder_stats failed".to_owned()),
+                data: None::<()>
+            };
+            Ok(HttpResponse::Ok().json(resp))
+        }
+    }
 }
\ No newline at end of file
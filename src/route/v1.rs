@@ -0,0 +1,292 @@
+//! Resource style routes under `/v1`. Unlike the legacy routes, failures are answered with the HTTP
+//! status of their [`BackendError`] code.
+use std::str::FromStr;
+use actix_web::{HttpResponse, web};
+use bigdecimal::BigDecimal;
+use serde::{Deserialize, Serialize};
+use crate::address::AccountAddress;
+use crate::campaign::CampaignPhase;
+use crate::db;
+use crate::route::BackendResponse;
+use crate::route::eligible::{check_eligibility, EligibleResp};
+use crate::route::err::{ApiError, BackendError};
+use crate::route::merkle::EligibleProofResp;
+use crate::route::stat::HolderStatsResp;
+use crate::server::AppState;
+use crate::tree::EligibleTree;
+
+/// Claim figures of the campaign, amounts in the token's base units.
+#[derive(Clone,Debug,Serialize,Deserialize)]
+pub struct StatsResp {
+    pub phase: String,
+    pub queried_addresses: u64,
+    pub claimed_number: u64,
+    pub claimed_amount: String,
+    pub holders: HolderStatsResp,
+}
+
+/// What the backend knows of an account, amounts in the token's base units. The leaf is only given
+/// while claiming.
+#[derive(Clone,Debug,Serialize,Deserialize)]
+pub struct AccountResp {
+    pub address: String,
+    pub queried: bool,
+    /// The amount computed when the account was last queried, before the allocations are scaled to
+    /// `TOTAL_BUDGET`, the leaf `amount` is what can be claimed.
+    pub claimable_amount: Option<String>,
+    pub query_time: Option<i64>,
+    pub leaf_index: Option<usize>,
+    pub amount: Option<String>,
+    pub proof: Option<Vec<String>>,
+    pub claimed: bool,
+    pub claimed_time: Option<i64>,
+    pub claimed_amount: Option<String>,
+    pub claim_tx_hash: Option<String>,
+}
+
+#[derive(Clone,Debug,Serialize,Deserialize)]
+pub struct BatchAccountsReq {
+    pub addresses: Vec<String>,
+}
+
+/// The leaf and the claim status of one address of a batch, or why they can't be given.
+#[derive(Clone,Debug,Serialize,Deserialize)]
+pub struct BatchAccountResp {
+    pub address: String,
+    pub amount: Option<String>,
+    pub leaf_index: Option<usize>,
+    pub proof: Option<Vec<String>>,
+    pub claimed: Option<bool>,
+    pub error: Option<String>,
+}
+
+fn ok<T: Clone + Serialize>(data: T) -> HttpResponse {
+    let resp = BackendResponse {
+        code: BackendError::Ok,
+        error: None,
+        data: Some(data)
+    };
+    HttpResponse::Ok().json(resp)
+}
+
+fn parse_address(address: &str) -> Result<AccountAddress, ApiError> {
+    AccountAddress::from_str(address)
+        .map_err(|_| ApiError::new(BackendError::InvalidParameters, "invalid address"))
+}
+
+fn internal_error(context: &str, e: anyhow::Error) -> ApiError {
+    log::warn!("{context} failed,{e}");
+    ApiError::new(BackendError::InternalErr, format!("{context} failed"))
+}
+
+pub async fn get_account(data: web::Data<AppState>, path: web::Path<String>)
+                         -> Result<HttpResponse, ApiError> {
+    let address = parse_address(&path)?;
+    let detail = db::get_account_detail(&data.db, &address.to_string()).await
+        .map_err(|e| internal_error("get_account_detail", e))?;
+    let leaf = if data.campaign.current() == CampaignPhase::Claiming {
+        let tree = data.eligible_tree().await
+            .map_err(|e| internal_error("load eligible tree", e))?;
+        tree.get(address.as_h160()).map(|(index, leaf)| (index, leaf.amount.clone(), leaf.proof.clone()))
+    } else {
+        None
+    };
+    let (leaf_index, amount, proof) = match leaf {
+        Some((index, amount, proof)) => (Some(index), Some(amount), Some(proof)),
+        None => (None, None, None),
+    };
+    let claimable_amount = match (detail.claimable_amount, detail.base_units) {
+        (Some(amount), Some(false)) => Some(data.reward_engine.scale(&BigDecimal::from_str(&amount.0.to_string())
+            .map_err(|e| internal_error("scale claimable amount", e.into()))?).base_units.to_string()),
+        (amount, _) => amount.map(|a| a.0.to_string()),
+    };
+    Ok(ok(AccountResp {
+        address: address.to_string(),
+        queried: detail.query_time.is_some(),
+        claimable_amount,
+        query_time: detail.query_time,
+        leaf_index,
+        amount,
+        proof,
+        claimed: detail.claimed_time.is_some(),
+        claimed_time: detail.claimed_time,
+        claimed_amount: detail.claimed_amount.map(|a| a.0.to_string()),
+        claim_tx_hash: detail.claim_tx_hash,
+    }))
+}
+
+/// JSON bytes allowed per address of a batch request: a quoted, comma separated address with some
+/// room for whitespace.
+const BATCH_BYTES_PER_ADDRESS: usize = 64;
+
+/// Size limit of the JSON bodies under `/v1`, large enough for a batch of `max_addresses` addresses.
+pub fn json_limit(max_addresses: usize) -> usize {
+    // the default of actix
+    let default = 32 * 1024;
+    std::cmp::max(default, 1024 + max_addresses * BATCH_BYTES_PER_ADDRESS)
+}
+
+/// Returns the amount, proof and claim status of each requested address, in request order, while
+/// claiming only. An invalid or ineligible address gets an error of its own.
+pub async fn post_accounts_batch(data: web::Data<AppState>, req: web::Json<BatchAccountsReq>)
+                                 -> Result<HttpResponse, ApiError> {
+    check_claiming(&data)?;
+    check_batch_size(req.addresses.len(), data.config.batch_max_addresses)?;
+    let mut valid = req.addresses.iter()
+        .filter_map(|a| AccountAddress::from_str(a).ok().map(|a| a.to_string()))
+        .collect::<Vec<_>>();
+    valid.sort();
+    valid.dedup();
+    let claimed = db::get_claimed_addresses(&data.db, &valid).await
+        .map_err(|e| internal_error("get_claimed_addresses", e))?;
+    let tree = data.eligible_tree().await
+        .map_err(|e| internal_error("load eligible tree", e))?;
+    Ok(ok(batch_accounts(&req.addresses, &tree, &claimed)))
+}
+
+fn check_batch_size(len: usize, max: usize) -> Result<(), ApiError> {
+    if len > max {
+        return Err(ApiError::new(BackendError::InvalidParameters, format!("too many addresses, at most {max}")));
+    }
+    Ok(())
+}
+
+/// The response of each of `addresses`, `claimed` being the claimed ones.
+fn batch_accounts(addresses: &[String], tree: &EligibleTree, claimed: &[String]) -> Vec<BatchAccountResp> {
+    addresses.iter()
+        .map(|address| match AccountAddress::from_str(address) {
+            Ok(address) => {
+                let leaf = tree.get(address.as_h160());
+                let address = address.to_string();
+                BatchAccountResp {
+                    amount: leaf.map(|(_, leaf)| leaf.amount.clone()),
+                    leaf_index: leaf.map(|(index, _)| index),
+                    proof: leaf.map(|(_, leaf)| leaf.proof.clone()),
+                    claimed: Some(claimed.contains(&address)),
+                    error: leaf.is_none().then(|| "account is not eligible".to_string()),
+                    address,
+                }
+            },
+            Err(_) => BatchAccountResp {
+                address: address.clone(),
+                amount: None,
+                leaf_index: None,
+                proof: None,
+                claimed: None,
+                error: Some("invalid address".to_string()),
+            },
+        })
+        .collect()
+}
+
+fn check_claiming(data: &AppState) -> Result<(), ApiError> {
+    match data.campaign.current() {
+        CampaignPhase::Claiming => Ok(()),
+        CampaignPhase::Ended => Err(ApiError::new(BackendError::WrongPhase, "claim ended")),
+        _ => Err(ApiError::new(BackendError::WrongPhase, "claim not start")),
+    }
+}
+
+/// Queries the eligibility of an account, during registration only.
+pub async fn get_account_eligibility(data: web::Data<AppState>, path: web::Path<String>)
+                                     -> Result<HttpResponse, ApiError> {
+    if data.campaign.current() != CampaignPhase::Registration {
+        return Err(ApiError::new(BackendError::WrongPhase, "registration ended"));
+    }
+    let address = parse_address(&path)?;
+    let eligibility: EligibleResp = check_eligibility(&data, &address).await
+        .map_err(|e| internal_error("eligibility provider query", e))?;
+    Ok(ok(eligibility))
+}
+
+/// Returns the amount and the proof of an account leaf, while claiming only.
+pub async fn get_account_proof(data: web::Data<AppState>, path: web::Path<String>)
+                               -> Result<HttpResponse, ApiError> {
+    check_claiming(&data)?;
+    let address = parse_address(&path)?;
+    let tree = data.eligible_tree().await
+        .map_err(|e| internal_error("load eligible tree", e))?;
+    let (_, leaf) = tree.get(address.as_h160())
+        .ok_or_else(|| ApiError::new(BackendError::NotFound, "account is not eligible"))?;
+    Ok(ok(EligibleProofResp {
+        address: address.to_string(),
+        amount: leaf.amount.clone(),
+        proof: leaf.proof.clone(),
+    }))
+}
+
+pub async fn get_stats(data: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    let queried_addresses = db::db_get_queried_addresses_number(&data.db).await
+        .map_err(|e| internal_error("get_queried_addresses_number", e))?;
+    let claimed_number = db::db_get_total_claimed_number(&data.db).await
+        .map_err(|e| internal_error("get_total_claimed_number", e))?;
+    let holders = db::db_get_holder_stats(&data.db, data.config.holder_stats_window_blocks).await
+        .map_err(|e| internal_error("get_holder_stats", e))?;
+    Ok(ok(StatsResp {
+        phase: data.campaign.current().as_ref().to_string(),
+        queried_addresses,
+        claimed_number,
+        claimed_amount: holders.claimed_amount.0.to_string(),
+        holders: HolderStatsResp::from(holders),
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::BATCH_MAX_ADDRESSES_LIMIT;
+    use crate::tree;
+    use super::*;
+
+    const ELIGIBLE: &str = "0x1111111111111111111111111111111111111111";
+    const CLAIMED: &str = "0x2222222222222222222222222222222222222222";
+
+    fn eligible_tree() -> EligibleTree {
+        let values = vec![
+            vec![ELIGIBLE.to_string(), "9".to_string()],
+            vec![CLAIMED.to_string(), "7".to_string()],
+        ];
+        EligibleTree::new(1, tree::build_tree(values)).unwrap()
+    }
+
+    #[test]
+    fn test_check_batch_size() {
+        assert!(check_batch_size(0, 2).is_ok());
+        assert!(check_batch_size(2, 2).is_ok());
+        let e = check_batch_size(3, 2).unwrap_err();
+        assert_eq!(e.code as i32, BackendError::InvalidParameters as i32);
+        assert_eq!(e.message, "too many addresses, at most 2");
+    }
+
+    #[test]
+    fn test_json_limit() {
+        assert_eq!(json_limit(1), 32 * 1024);
+        // a batch of the most addresses allowed, each in its longest form
+        let addresses = vec![format!("0X{}", "A".repeat(40)); BATCH_MAX_ADDRESSES_LIMIT];
+        let body = serde_json::to_string_pretty(&BatchAccountsReq { addresses }).unwrap();
+        assert!(body.len() <= json_limit(BATCH_MAX_ADDRESSES_LIMIT));
+    }
+
+    #[test]
+    fn test_batch_accounts() {
+        let tree = eligible_tree();
+        let addresses = [ELIGIBLE.to_uppercase().replace("0X", "0x"), "0x1234".to_string(),
+            "0x3333333333333333333333333333333333333333".to_string(), CLAIMED.to_string()];
+        let accounts = batch_accounts(&addresses, &tree, &[CLAIMED.to_string()]);
+        assert_eq!(accounts.len(), 4);
+
+        assert_eq!(accounts[0].address, ELIGIBLE);
+        assert_eq!(accounts[0].amount.as_deref(), Some("9"));
+        assert_eq!(accounts[0].leaf_index, tree.get(AccountAddress::from_str(ELIGIBLE).unwrap().as_h160()).map(|(i, _)| i));
+        assert!(accounts[0].proof.is_some());
+        assert_eq!((accounts[0].claimed, accounts[0].error.as_deref()), (Some(false), None));
+
+        assert_eq!(accounts[1].address, "0x1234");
+        assert_eq!((accounts[1].amount.as_deref(), accounts[1].claimed), (None, None));
+        assert_eq!(accounts[1].error.as_deref(), Some("invalid address"));
+
+        assert_eq!((accounts[2].amount.as_deref(), accounts[2].claimed), (None, Some(false)));
+        assert_eq!(accounts[2].error.as_deref(), Some("account is not eligible"));
+
+        assert_eq!((accounts[3].amount.as_deref(), accounts[3].claimed), (Some("7"), Some(true)));
+    }
+}
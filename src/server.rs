@@ -1,20 +1,44 @@
 use actix_web::{HttpServer, web};
 use std::net::SocketAddr;
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 use actix_web::App;
 use std::thread;
 use actix_cors::Cors;
-use merkle_tree_rs::standard::StandardMerkleTree;
+use tokio::sync::OnceCell;
+use crate::campaign::CampaignState;
 use crate::config::Config;
+use crate::tree;
+use crate::tree::EligibleTree;
+use crate::eligibility::EligibilityProvider;
+use crate::reward::RewardEngine;
+use crate::route::admin::get_claim_anomalies;
 use crate::route::eligible::get_eligible;
 use crate::route::merkle::{get_eligible_proof, get_eligible_tree_root};
-use crate::route::stat::{get_queried_addresses_number, get_total_claimed_amount, get_total_claimed_number};
+use crate::route::err::{ApiError, BackendError};
+use crate::route::v1;
+use crate::route::stat::{get_holder_stats, get_queried_addresses_number, get_total_claimed_amount, get_total_claimed_number};
 
 #[derive(Clone)]
 pub struct AppState {
     pub config: Config,
     pub db: rbatis::RBatis,
-    pub eligible_tree: Option<Arc<Mutex<StandardMerkleTree>>>,
+    pub campaign: CampaignState,
+    pub eligible_tree: Arc<OnceCell<Arc<EligibleTree>>>,
+    pub eligibility_provider: Arc<dyn EligibilityProvider>,
+    pub reward_engine: Arc<RewardEngine>,
+}
+
+impl AppState {
+    /// Returns the eligible tree of the pinned snapshot, loading it on first use once the snapshot is frozen.
+    pub async fn eligible_tree(&self) -> anyhow::Result<Arc<EligibleTree>> {
+        let tree = self.eligible_tree
+            .get_or_try_init(|| async {
+                tree::load_eligible_tree(&self.db, self.config.merkle_snapshot_version)
+                    .await.map(Arc::new)
+            })
+            .await?;
+        Ok(tree.clone())
+    }
 }
 
 pub async fn run_server(app_state: AppState) {
@@ -30,8 +54,9 @@ pub async fn run_server(app_state: AppState) {
 
 pub async fn run_rpc_server(app_state: AppState) {
     let works_number = app_state.config.workers;
+    let batch_max_addresses = app_state.config.batch_max_addresses;
     let bind_to = SocketAddr::new("0.0.0.0".parse().unwrap(),
-                                  app_state.config.port as u16);
+                                  app_state.config.port);
     HttpServer::new(move || {
         let cors = Cors::permissive();
         App::new()
@@ -41,8 +66,19 @@ pub async fn run_rpc_server(app_state: AppState) {
             .route("/get_queried_addresses_number", web::get().to(get_queried_addresses_number))
             .route("/get_total_claimed_number", web::get().to(get_total_claimed_number))
             .route("/get_total_claimed_amount", web::get().to(get_total_claimed_amount))
+            .route("/get_holder_stats", web::get().to(get_holder_stats))
             .route("/get_eligible_tree_root", web::get().to(get_eligible_tree_root))
             .route("/get_eligible_proof", web::get().to(get_eligible_proof))
+            .route("/admin/claim_anomalies", web::get().to(get_claim_anomalies))
+            .service(web::scope("/v1")
+                .app_data(web::JsonConfig::default().limit(v1::json_limit(batch_max_addresses)).error_handler(|e, _| {
+                    ApiError::new(BackendError::InvalidParameters, e.to_string()).into()
+                }))
+                .route("/accounts/batch", web::post().to(v1::post_accounts_batch))
+                .route("/accounts/{address}", web::get().to(v1::get_account))
+                .route("/accounts/{address}/eligibility", web::get().to(v1::get_account_eligibility))
+                .route("/accounts/{address}/proof", web::get().to(v1::get_account_proof))
+                .route("/stats", web::get().to(v1::get_stats)))
     })
         .workers(works_number as usize)
         .bind(&bind_to)
@@ -0,0 +1,8 @@
+-- current phase of the campaign, a single row table
+CREATE TABLE campaign_phase (
+    id smallint NOT NULL DEFAULT 1,
+    phase text NOT NULL,
+    updated_at bigint NOT NULL,
+    PRIMARY KEY (id),
+    CHECK (id = 1)
+);
@@ -0,0 +1,9 @@
+-- frozen eligible trees, the served one is pinned by version
+CREATE TABLE merkle_snapshots (
+    version bigint NOT NULL,
+    root text NOT NULL,
+    leaf_encoding text NOT NULL,
+    leaves text NOT NULL,
+    created_at bigint NOT NULL,
+    PRIMARY KEY (version)
+);
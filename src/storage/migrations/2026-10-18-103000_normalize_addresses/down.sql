@@ -0,0 +1,2 @@
+-- normalized addresses are left as they are
+SELECT 1;
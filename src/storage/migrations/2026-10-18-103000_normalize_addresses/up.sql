@@ -0,0 +1,14 @@
+-- addresses are stored as 0x followed by 40 lowercase hex digits
+
+-- rows written with an elided address (0x1234…abcd) can't be recovered
+DELETE FROM query_accounts WHERE address LIKE '%…%';
+DELETE FROM claimed_accounts WHERE address LIKE '%…%';
+-- claims are re-indexed from SYNC_START_BLOCK to restore the deleted ones
+DELETE FROM last_sync_block;
+
+-- keep the latest query of addresses that only differ by case or by the 0x prefix
+DELETE FROM query_accounts a USING query_accounts b
+WHERE lower(regexp_replace(a.address, '^0[xX]', '')) = lower(regexp_replace(b.address, '^0[xX]', ''))
+  AND a.address <> b.address
+  AND (a.query_time < b.query_time OR (a.query_time = b.query_time AND a.address < b.address));
+UPDATE query_accounts SET address = '0x' || lower(regexp_replace(address, '^0[xX]', ''));
@@ -0,0 +1,2 @@
+ALTER TABLE query_accounts DROP COLUMN base_units;
+ALTER TABLE query_accounts DROP COLUMN eth_gas_cost;
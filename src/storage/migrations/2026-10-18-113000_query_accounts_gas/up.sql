@@ -0,0 +1,5 @@
+-- the gas the claimable amount was computed from, NULL when the amount was pre-computed
+ALTER TABLE query_accounts ADD COLUMN eth_gas_cost numeric;
+-- whether the claimable amount is in token base units, the rows queried before are in whole tokens
+ALTER TABLE query_accounts ADD COLUMN base_units boolean NOT NULL DEFAULT false;
+ALTER TABLE query_accounts ALTER COLUMN base_units SET DEFAULT true;
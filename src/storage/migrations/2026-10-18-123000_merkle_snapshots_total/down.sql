@@ -0,0 +1 @@
+ALTER TABLE merkle_snapshots DROP COLUMN total_amount;
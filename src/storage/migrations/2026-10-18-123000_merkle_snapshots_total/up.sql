@@ -0,0 +1,2 @@
+-- sum of the leaf amounts, after scaling to the total budget
+ALTER TABLE merkle_snapshots ADD COLUMN total_amount numeric;
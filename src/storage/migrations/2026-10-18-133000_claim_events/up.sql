@@ -0,0 +1,16 @@
+-- every Claimed log, claimed_accounts becomes the per address aggregate of it
+CREATE TABLE claim_events (
+    tx_hash text NOT NULL,
+    log_index bigint NOT NULL,
+    block_number bigint NOT NULL,
+    block_hash text NOT NULL,
+    address text NOT NULL,
+    amount numeric NOT NULL,
+    claimed_time bigint NOT NULL,
+    PRIMARY KEY (tx_hash, log_index)
+);
+CREATE INDEX claim_events_address ON claim_events (address);
+CREATE INDEX claim_events_block_number ON claim_events (block_number);
+
+-- resync from SYNC_START_BLOCK so past claims are recorded as events
+DELETE FROM last_sync_block;
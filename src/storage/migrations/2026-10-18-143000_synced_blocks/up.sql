@@ -0,0 +1,6 @@
+-- hashes of the recently synced blocks, to detect the chain reorganizing below the sync checkpoint
+CREATE TABLE synced_blocks (
+    block_number bigint NOT NULL,
+    block_hash text NOT NULL,
+    PRIMARY KEY (block_number)
+);
@@ -0,0 +1 @@
+DROP TABLE distributor_events;
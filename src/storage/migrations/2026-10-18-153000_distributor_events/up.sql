@@ -0,0 +1,11 @@
+-- distributor events other than Claimed, their decoded parameters as a JSON object
+CREATE TABLE distributor_events (
+    tx_hash text NOT NULL,
+    log_index bigint NOT NULL,
+    block_number bigint NOT NULL,
+    block_hash text NOT NULL,
+    event_name text NOT NULL,
+    params jsonb NOT NULL,
+    PRIMARY KEY (tx_hash, log_index)
+);
+CREATE INDEX distributor_events_name_block ON distributor_events (event_name, block_number);
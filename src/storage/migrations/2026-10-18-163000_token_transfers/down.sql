@@ -0,0 +1,2 @@
+DROP TABLE token_balances;
+DROP TABLE token_transfers;
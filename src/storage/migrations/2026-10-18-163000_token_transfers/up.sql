@@ -0,0 +1,22 @@
+-- Transfer logs of the token, token_balances is the per address aggregate of them
+CREATE TABLE token_transfers (
+    tx_hash text NOT NULL,
+    log_index bigint NOT NULL,
+    block_number bigint NOT NULL,
+    block_hash text NOT NULL,
+    from_address text NOT NULL,
+    to_address text NOT NULL,
+    amount numeric NOT NULL,
+    PRIMARY KEY (tx_hash, log_index)
+);
+CREATE INDEX token_transfers_from_address ON token_transfers (from_address);
+CREATE INDEX token_transfers_to_address ON token_transfers (to_address);
+CREATE INDEX token_transfers_block_number ON token_transfers (block_number);
+
+CREATE TABLE token_balances (
+    address text NOT NULL,
+    balance numeric NOT NULL,
+    PRIMARY KEY (address)
+);
+
+-- the transfers of the blocks synced before are indexed by `pdoge sync --from <block>`
@@ -0,0 +1,17 @@
+-- indexed claims which don't match the eligible tree, a claim may have several kinds of anomaly
+CREATE TABLE claim_anomalies (
+    tx_hash text NOT NULL,
+    log_index bigint NOT NULL,
+    kind text NOT NULL,
+    block_number bigint NOT NULL,
+    address text NOT NULL,
+    claimed_amount numeric NOT NULL,
+    expected_amount numeric,
+    snapshot_version bigint NOT NULL,
+    detected_at bigint NOT NULL,
+    PRIMARY KEY (tx_hash, log_index, kind)
+);
+CREATE INDEX claim_anomalies_kind ON claim_anomalies (kind);
+CREATE INDEX claim_anomalies_block_number ON claim_anomalies (block_number);
+
+-- the claims indexed before are checked with the next synced claims, see claim_events.checked
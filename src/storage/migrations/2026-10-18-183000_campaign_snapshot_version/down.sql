@@ -0,0 +1 @@
+ALTER TABLE campaign_phase DROP COLUMN snapshot_version;
@@ -0,0 +1,6 @@
+-- the snapshot frozen when the campaign entered snapshot_frozen, served unless MERKLE_SNAPSHOT_VERSION pins another
+ALTER TABLE campaign_phase ADD COLUMN snapshot_version bigint;
+
+-- campaigns frozen before keep the latest snapshot, which is the one they served
+UPDATE campaign_phase SET snapshot_version = (SELECT max(version) FROM merkle_snapshots)
+WHERE phase <> 'registration';
@@ -0,0 +1,5 @@
+DELETE FROM claim_events WHERE NOT confirmed;
+ALTER TABLE claim_events DROP COLUMN confirmed;
+DELETE FROM claimed_accounts;
+INSERT INTO claimed_accounts (address,claimed_time,claimed_amount)
+SELECT address,min(claimed_time),sum(amount) FROM claim_events GROUP BY address;
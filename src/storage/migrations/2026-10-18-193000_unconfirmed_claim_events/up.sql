@@ -0,0 +1,3 @@
+-- claims received from the Claimed logs subscription before CONFIRMATIONS blocks were mined on top of them,
+-- confirmed or discarded once the sync reaches their block
+ALTER TABLE claim_events ADD COLUMN confirmed boolean NOT NULL DEFAULT true;
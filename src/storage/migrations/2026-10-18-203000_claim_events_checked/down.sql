@@ -0,0 +1 @@
+ALTER TABLE claim_events DROP COLUMN checked;
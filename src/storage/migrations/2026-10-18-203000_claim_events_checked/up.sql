@@ -0,0 +1,3 @@
+-- whether the anomalies of a confirmed claim were looked for, claims stay unchecked while the eligible
+-- tree can't be loaded and are checked with the next synced claims
+ALTER TABLE claim_events ADD COLUMN checked boolean NOT NULL DEFAULT false;
@@ -0,0 +1,150 @@
+use anyhow::format_err;
+use rbatis::executor::Executor;
+use rbatis::RBatis;
+use crate::db;
+use crate::db::tables::SchemaMigration;
+
+/// A migration of `storage/migrations`, embedded in the binary.
+pub struct Migration {
+    /// The Diesel version of the migration, the digits of its directory name timestamp.
+    pub version: &'static str,
+    pub name: &'static str,
+    pub up: &'static str,
+    pub down: &'static str,
+}
+
+macro_rules! migration {
+    ($version:literal, $dir:literal) => {
+        Migration {
+            version: $version,
+            name: $dir,
+            up: include_str!(concat!("migrations/", $dir, "/up.sql")),
+            down: include_str!(concat!("migrations/", $dir, "/down.sql")),
+        }
+    };
+}
+
+/// Every migration, in the order they are applied.
+pub const MIGRATIONS: &[Migration] = &[
+    migration!("00000000000000", "00000000000000_diesel_initial_setup"),
+    migration!("20230428024756", "2023-04-28-024756_init"),
+    migration!("20261018081500", "2026-10-18-081500_campaign_phase"),
+    migration!("20261018093000", "2026-10-18-093000_merkle_snapshots"),
+    migration!("20261018103000", "2026-10-18-103000_normalize_addresses"),
+    migration!("20261018113000", "2026-10-18-113000_query_accounts_gas"),
+    migration!("20261018123000", "2026-10-18-123000_merkle_snapshots_total"),
+    migration!("20261018133000", "2026-10-18-133000_claim_events"),
+    migration!("20261018143000", "2026-10-18-143000_synced_blocks"),
+    migration!("20261018153000", "2026-10-18-153000_distributor_events"),
+    migration!("20261018163000", "2026-10-18-163000_token_transfers"),
+    migration!("20261018173000", "2026-10-18-173000_claim_anomalies"),
+    migration!("20261018183000", "2026-10-18-183000_campaign_snapshot_version"),
+    migration!("20261018193000", "2026-10-18-193000_unconfirmed_claim_events"),
+    migration!("20261018203000", "2026-10-18-203000_claim_events_checked"),
+];
+
+/// Migrations that created the schema before migrations were tracked by the binary.
+const BASELINE_VERSIONS: [&str; 2] = ["00000000000000", "20230428024756"];
+
+/// Key of the advisory lock migrating processes take, so that processes started together don't run
+/// the same migration.
+const MIGRATION_LOCK: i64 = 0x0070_646f_6765;
+
+/// Waits for the other migrating processes, until the transaction of `rb` ends.
+async fn lock_migrations(rb: &dyn Executor) -> anyhow::Result<()> {
+    rb.query("select pg_advisory_xact_lock(?)", vec![rbs::to_value!(MIGRATION_LOCK)]).await?;
+    Ok(())
+}
+
+/// Creates the table of applied migrations, the one the Diesel CLI uses, so databases
+/// migrated with it are picked up. A schema created by hand is marked as migrated up to `init`.
+async fn init_migrations_table(rb: &RBatis) -> anyhow::Result<()> {
+    let tx = rb.acquire_begin().await?;
+    let ret = async {
+        lock_migrations(&tx).await?;
+        let exists: bool = rbatis::decode(tx
+            .query("select to_regclass('__diesel_schema_migrations') is not null",vec![])
+            .await?)?;
+        if exists {
+            return Ok(());
+        }
+        tx.exec("create table __diesel_schema_migrations (\
+            version varchar(50) primary key not null,\
+            run_on timestamp not null default current_timestamp)",vec![])
+            .await?;
+        let has_schema: bool = rbatis::decode(tx
+            .query("select to_regclass('query_accounts') is not null",vec![])
+            .await?)?;
+        if has_schema {
+            for version in BASELINE_VERSIONS {
+                tx.exec("insert into __diesel_schema_migrations (version) values (?)",
+                        vec![rbs::to_value!(version)])
+                    .await?;
+            }
+            log::info!("existing schema marked as migrated up to {}", BASELINE_VERSIONS[1]);
+        }
+        Ok(())
+    }.await;
+    db::finish_transaction(tx, ret).await
+}
+
+pub async fn applied_versions(rb: &RBatis) -> anyhow::Result<Vec<String>> {
+    init_migrations_table(rb).await?;
+    let applied: Vec<SchemaMigration> = rb
+        .query_decode("select version from __diesel_schema_migrations order by version asc",vec![])
+        .await?;
+    Ok(applied.into_iter().map(|m| m.version).collect())
+}
+
+/// Applies every pending migration, each in its own transaction, and returns their names.
+pub async fn run_pending_migrations(rb: &RBatis) -> anyhow::Result<Vec<&'static str>> {
+    let applied = applied_versions(rb).await?;
+    let mut ran = vec![];
+    for migration in MIGRATIONS.iter().filter(|m| !applied.iter().any(|v| v == m.version)) {
+        let ran_here = apply(rb, migration.up, "insert into __diesel_schema_migrations (version) values (?)", migration.version, false)
+            .await
+            .map_err(|e| format_err!("migration {} failed,{:?}", migration.name, e))?;
+        if ran_here {
+            log::info!("migration {} applied", migration.name);
+            ran.push(migration.name);
+        }
+    }
+    Ok(ran)
+}
+
+/// Reverts the `steps` last applied migrations, each in its own transaction, and returns their names.
+pub async fn revert_migrations(rb: &RBatis, steps: usize) -> anyhow::Result<Vec<&'static str>> {
+    let applied = applied_versions(rb).await?;
+    let mut reverted = vec![];
+    for version in applied.iter().rev().take(steps) {
+        let migration = MIGRATIONS.iter().find(|m| m.version == version)
+            .ok_or_else(|| format_err!("applied migration {} is unknown to this binary", version))?;
+        let ran_here = apply(rb, migration.down, "delete from __diesel_schema_migrations where version = ?", migration.version, true)
+            .await
+            .map_err(|e| format_err!("revert migration {} failed,{:?}", migration.name, e))?;
+        if ran_here {
+            log::info!("migration {} reverted", migration.name);
+            reverted.push(migration.name);
+        }
+    }
+    Ok(reverted)
+}
+
+/// Runs `sql` and records it with `record_sql` if, once the other migrating processes are done,
+/// `version` is still as `applied` says, another process may have run it meanwhile. Returns whether it ran.
+async fn apply(rb: &RBatis, sql: &str, record_sql: &str, version: &str, applied: bool) -> anyhow::Result<bool> {
+    let tx = rb.acquire_begin().await?;
+    let ret = async {
+        lock_migrations(&tx).await?;
+        let recorded: u64 = rbatis::decode(tx
+            .query("select count(1) from __diesel_schema_migrations where version = ?", vec![rbs::to_value!(version)])
+            .await?)?;
+        if (recorded > 0) != applied {
+            return Ok(false);
+        }
+        tx.exec(sql, vec![]).await?;
+        tx.exec(record_sql, vec![rbs::to_value!(version)]).await?;
+        Ok(true)
+    }.await;
+    db::finish_transaction(tx, ret).await
+}
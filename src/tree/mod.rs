@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+use anyhow::format_err;
+use bigdecimal::{BigDecimal, Zero};
+use ethabi::Address;
+use merkle_tree_rs::standard::StandardMerkleTree;
+use num::BigInt;
+use rbatis::rbdc::decimal::Decimal;
+use rbatis::executor::Executor;
+use rbatis::RBatis;
+use serde::{Deserialize, Serialize};
+use crate::amount::scale_to_budget;
+use crate::db;
+use crate::db::tables::{MerkleSnapshot, QueryAccount};
+use crate::reward::RewardEngine;
+
+pub const LEAF_ENCODING: [&str; 2] = ["address", "uint256"];
+
+#[derive(Clone, Debug)]
+pub struct EligibleLeaf {
+    pub address: String,
+    pub amount: String,
+    pub proof: Vec<String>,
+}
+
+/// A frozen eligible tree with every proof computed up front, so lookups are a map access.
+#[derive(Debug)]
+pub struct EligibleTree {
+    version: i64,
+    root: String,
+    tree: StandardMerkleTree,
+    leaves: Vec<EligibleLeaf>,
+    index: HashMap<Address, usize>,
+    total_amount: BigInt,
+}
+
+impl EligibleTree {
+    pub fn new(version: i64, tree: StandardMerkleTree) -> anyhow::Result<Self> {
+        let dump = TreeDump::of(&tree)?;
+        let mut leaves = Vec::with_capacity(dump.values.len());
+        let mut index = HashMap::with_capacity(dump.values.len());
+        let mut total_amount = BigInt::zero();
+        for (i, value) in dump.values.into_iter().enumerate() {
+            let address = Address::from_str(&value.value[0])
+                .map_err(|e| format_err!("invalid leaf address {},{:?}", value.value[0], e))?;
+            index.insert(address, i);
+            total_amount += BigInt::from_str(&value.value[1])
+                .map_err(|e| format_err!("invalid leaf amount {},{:?}", value.value[1], e))?;
+            leaves.push(EligibleLeaf {
+                address: value.value[0].clone(),
+                amount: value.value[1].clone(),
+                proof: proof_of(&dump.tree, value.tree_index),
+            });
+        }
+        Ok(Self {
+            version,
+            root: tree.root(),
+            tree,
+            leaves,
+            index,
+            total_amount,
+        })
+    }
+
+    pub fn version(&self) -> i64 {
+        self.version
+    }
+
+    pub fn root(&self) -> &str {
+        &self.root
+    }
+
+    pub fn tree(&self) -> &StandardMerkleTree {
+        &self.tree
+    }
+
+    /// Sum of the amounts of every leaf, in base units.
+    pub fn total_amount(&self) -> &BigInt {
+        &self.total_amount
+    }
+
+    pub fn leaves(&self) -> &[EligibleLeaf] {
+        &self.leaves
+    }
+
+    /// Returns the leaf index and the leaf of `address`.
+    pub fn get(&self, address: &Address) -> Option<(usize, &EligibleLeaf)> {
+        self.index.get(address).map(|i| (*i, &self.leaves[*i]))
+    }
+}
+
+/// Mirror of `StandardMerkleTreeData`, whose fields are private. It serializes to the
+/// OpenZeppelin `standard-v1` tree dump format, with camel case keys.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all(serialize = "camelCase"))]
+pub struct TreeDump {
+    pub format: String,
+    pub tree: Vec<String>,
+    pub values: Vec<TreeDumpValue>,
+    pub leaf_encoding: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all(serialize = "camelCase"))]
+pub struct TreeDumpValue {
+    pub value: Vec<String>,
+    pub tree_index: usize,
+}
+
+impl TreeDump {
+    pub fn of(tree: &StandardMerkleTree) -> anyhow::Result<Self> {
+        Ok(serde_json::from_value(serde_json::to_value(tree.dump())?)?)
+    }
+}
+
+/// Sibling hashes from the node at `index` up to the root, as `StandardMerkleTree::get_proof`
+/// returns them, without cloning the tree for every leaf.
+fn proof_of(tree: &[String], mut index: usize) -> Vec<String> {
+    let mut proof = vec![];
+    while index > 0 {
+        let sibling = if index % 2 == 1 { index + 1 } else { index - 1 };
+        proof.push(tree[sibling].clone());
+        index = (index - 1) / 2;
+    }
+    proof
+}
+
+/// Leaf values of the queried accounts, as a snapshot frozen now would have them.
+pub async fn queried_tree_values(rb: &RBatis, engine: &RewardEngine) -> anyhow::Result<Vec<Vec<String>>> {
+    let allocations = queried_allocations(rb, engine).await?;
+    Ok(tree_values(allocations, engine))
+}
+
+/// Saves the tree of `tree_values` as a new snapshot version and returns it.
+pub async fn save_snapshot(rb: &dyn Executor, tree_values: Vec<Vec<String>>) -> anyhow::Result<EligibleTree> {
+    let tree = build_tree(tree_values.clone());
+    let total_amount = total_amount(&tree_values);
+    let created_at = SystemTime::now().duration_since(UNIX_EPOCH)
+        .expect("Time went backwards").as_secs() as i64;
+    let version = db::save_merkle_snapshot(rb, MerkleSnapshot {
+        version: 0,
+        root: tree.root(),
+        leaf_encoding: serde_json::to_string(&LEAF_ENCODING)?,
+        leaves: serde_json::to_string(&tree_values)?,
+        created_at,
+        total_amount: Some(Decimal::from_str(&total_amount.to_string())?),
+    }).await?;
+    log::info!("eligible tree snapshot {} frozen with {} leaves, root {}, total amount {}",
+        version, tree_values.len(), tree.root(), total_amount);
+    EligibleTree::new(version, tree)
+}
+
+/// Builds the tree of `tree_values`, without saving it.
+pub fn build_tree(tree_values: Vec<Vec<String>>) -> StandardMerkleTree {
+    StandardMerkleTree::of(tree_values, &leaf_encoding())
+}
+
+pub fn total_amount(tree_values: &[Vec<String>]) -> BigInt {
+    tree_values.iter()
+        .map(|v| BigInt::from_str(&v[1]).expect("tree values amounts are integers"))
+        .sum()
+}
+
+/// Leaf values of `allocations`, scaled down pro-rata to the total budget of `engine` if any.
+pub fn tree_values(allocations: Vec<(String, BigInt)>, engine: &RewardEngine) -> Vec<Vec<String>> {
+    let (addresses, mut amounts): (Vec<_>, Vec<_>) = allocations.into_iter().unzip();
+    if let Some(budget) = &engine.total_budget {
+        let raw_total: BigInt = amounts.iter().sum();
+        if &raw_total > budget {
+            log::info!("allocations total {} exceeds the budget {}, scaling them down", raw_total, budget);
+            amounts = scale_to_budget(&amounts, budget);
+        }
+    }
+    addresses.into_iter().zip(amounts)
+        .map(|(address, amount)| vec![address, amount.to_string()])
+        .collect()
+}
+
+/// The snapshot pinned by `version`, or else the one frozen when the campaign entered `SnapshotFrozen`.
+/// Snapshots saved later, by `build-tree --freeze`, are only served when pinned.
+pub async fn snapshot_version(rb: &RBatis, version: Option<i64>) -> anyhow::Result<i64> {
+    match version {
+        Some(version) => Ok(version),
+        None => db::get_campaign_snapshot_version(rb).await?
+            .ok_or_else(|| format_err!("no merkle snapshot was frozen for the campaign, pin one with MERKLE_SNAPSHOT_VERSION")),
+    }
+}
+
+/// Loads the tree of the snapshot of [`snapshot_version`].
+pub async fn load_eligible_tree(rb: &RBatis, version: Option<i64>) -> anyhow::Result<EligibleTree> {
+    load_snapshot_tree(rb, snapshot_version(rb, version).await?).await
+}
+
+pub async fn load_snapshot_tree(rb: &RBatis, version: i64) -> anyhow::Result<EligibleTree> {
+    let snapshot = db::get_merkle_snapshot(rb, version).await?
+        .ok_or_else(|| format_err!("merkle snapshot version {} not found", version))?;
+    let tree = tree_from_snapshot(&snapshot)?;
+    let tree = EligibleTree::new(snapshot.version, tree)?;
+    log::info!("eligible tree snapshot {} loaded, root {}, total amount {}",
+        snapshot.version, snapshot.root, tree.total_amount());
+    Ok(tree)
+}
+
+/// Rebuilds the tree of a snapshot and checks it still has the stored root.
+pub fn tree_from_snapshot(snapshot: &MerkleSnapshot) -> anyhow::Result<StandardMerkleTree> {
+    let leaf_encoding: Vec<String> = serde_json::from_str(&snapshot.leaf_encoding)?;
+    let leaves: Vec<Vec<String>> = serde_json::from_str(&snapshot.leaves)?;
+    let tree = StandardMerkleTree::of(leaves, &leaf_encoding);
+    if tree.root() != snapshot.root {
+        return Err(format_err!("merkle snapshot {} root mismatch, stored {} rebuilt {}",
+            snapshot.version, snapshot.root, tree.root()));
+    }
+    Ok(tree)
+}
+
+/// Allocations of the queried accounts, in address order. Amounts queried from gas are
+/// computed again with the current reward formula, pre-computed amounts are kept as they are.
+pub async fn queried_allocations(rb: &RBatis, engine: &RewardEngine) -> anyhow::Result<Vec<(String, BigInt)>> {
+    let accounts_eligible = db::get_all_queried_accounts(rb).await?;
+    let mut allocations = vec![];
+    for ae in accounts_eligible {
+        let claimable_amount = match &ae.eth_gas_cost {
+            Some(gas) => engine.claimable_amount(&BigDecimal::from_str(gas)?).base_units,
+            None if ae.base_units => BigDecimal::from_str(&ae.claimable_amount)?.with_scale(0).as_bigint_and_exponent().0,
+            // queried before amounts were scaled by the token decimals
+            None => engine.scale(&BigDecimal::from_str(&ae.claimable_amount)?).base_units,
+        };
+        allocations.push((ae.address, claimable_amount));
+    }
+    //because the merkle proof should be made from index 1,we add a zero account on the index 0 of tree node
+    if allocations.is_empty() {
+        let zero_account = QueryAccount::default();
+        allocations.push((zero_account.address.clone(), BigInt::zero()));
+        db::save_query_account(rb.clone(),zero_account).await?;
+    }
+    Ok(allocations)
+}
+
+fn leaf_encoding() -> Vec<String> {
+    LEAF_ENCODING.iter().map(|t| t.to_string()).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use merkle_tree_rs::standard::LeafType;
+
+    #[test]
+    fn test_precomputed_proofs_match_tree() {
+        let values = (1..=7u64)
+            .map(|i| vec![format!("{:?}", Address::from_low_u64_be(i)), (i * 100).to_string()])
+            .collect::<Vec<_>>();
+        let tree = StandardMerkleTree::of(values.clone(), &leaf_encoding());
+        let eligible_tree = EligibleTree::new(1, tree.clone()).unwrap();
+        for (i, value) in values.iter().enumerate() {
+            let address = Address::from_str(&value[0]).unwrap();
+            let (index, leaf) = eligible_tree.get(&address).unwrap();
+            assert_eq!(index, i);
+            assert_eq!(leaf.amount, value[1]);
+            assert_eq!(leaf.proof, tree.get_proof(LeafType::Number(i)));
+        }
+        assert!(eligible_tree.get(&Address::from_low_u64_be(8)).is_none());
+    }
+}
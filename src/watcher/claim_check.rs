@@ -0,0 +1,150 @@
+use std::fmt::{Debug, Formatter};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use bigdecimal::BigDecimal;
+use rbatis::executor::Executor;
+use rbatis::RBatis;
+use rbatis::rbdc::decimal::Decimal;
+use tokio::sync::Mutex;
+use crate::address::AccountAddress;
+use crate::db;
+use crate::db::tables::ClaimAnomaly;
+use crate::tree;
+use crate::tree::EligibleTree;
+
+/// What is wrong with an indexed claim, compared to the eligible tree.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum AnomalyKind {
+    /// The address has no leaf in the tree.
+    UnknownAddress,
+    /// The claimed amount is not the amount of the address leaf.
+    WrongAmount,
+    /// The address had already claimed.
+    DoubleClaim,
+}
+
+impl Debug for AnomalyKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_ref())
+    }
+}
+
+impl AsRef<str> for AnomalyKind {
+    fn as_ref(&self) -> &'static str {
+        match self {
+            AnomalyKind::UnknownAddress => "unknown_address",
+            AnomalyKind::WrongAmount => "wrong_amount",
+            AnomalyKind::DoubleClaim => "double_claim",
+        }
+    }
+}
+
+/// Anomalies of a claim of `claimed` tokens, given the amount the tree allocates to the address,
+/// if any, and whether an earlier claim of the address was indexed.
+pub fn check_claim(expected: Option<&BigDecimal>, claimed: &BigDecimal, claimed_before: bool) -> Vec<AnomalyKind> {
+    let mut anomalies = vec![];
+    match expected {
+        None => anomalies.push(AnomalyKind::UnknownAddress),
+        Some(expected) if expected != claimed => anomalies.push(AnomalyKind::WrongAmount),
+        Some(_) => {},
+    }
+    if claimed_before {
+        anomalies.push(AnomalyKind::DoubleClaim);
+    }
+    anomalies
+}
+
+/// Checks indexed claims against the leaves of the active eligible tree snapshot.
+pub struct ClaimChecker {
+    db: RBatis,
+    snapshot_version: Option<i64>,
+    /// The tree of the last active snapshot, reloaded when another snapshot becomes active.
+    tree: Mutex<Option<Arc<EligibleTree>>>,
+}
+
+impl ClaimChecker {
+    pub fn new(db: RBatis, snapshot_version: Option<i64>) -> Self {
+        Self {
+            db,
+            snapshot_version,
+            tree: Mutex::new(None),
+        }
+    }
+
+    async fn tree(&self) -> anyhow::Result<Arc<EligibleTree>> {
+        let version = tree::snapshot_version(&self.db, self.snapshot_version).await?;
+        let mut tree = self.tree.lock().await;
+        if let Some(tree) = tree.as_ref().filter(|t| t.version() == version) {
+            return Ok(tree.clone());
+        }
+        let loaded = Arc::new(tree::load_snapshot_tree(&self.db, version).await?);
+        *tree = Some(loaded.clone());
+        Ok(loaded)
+    }
+
+    /// Records the anomalies of the confirmed claims not checked yet, in the transaction of `rb` which
+    /// saved the latest ones. While the tree can't be loaded claims are left unchecked, indexing goes on
+    /// and they are checked with the next synced claims.
+    pub async fn check(&self, rb: &dyn Executor) -> anyhow::Result<()> {
+        let tree = match self.tree().await {
+            Ok(tree) => tree,
+            Err(e) => {
+                log::warn!("indexed claims left unchecked, eligible tree unavailable,{:?}", e);
+                return Ok(());
+            }
+        };
+        let events = db::get_unchecked_claim_events(rb).await?;
+        if events.is_empty() {
+            return Ok(());
+        }
+        let mut addresses = events.iter().map(|e| e.address.clone()).collect::<Vec<_>>();
+        addresses.sort();
+        addresses.dedup();
+        let first_claims = db::get_first_claim_events(rb, addresses).await?;
+        let detected_at = SystemTime::now().duration_since(UNIX_EPOCH).expect("Time went backwards").as_secs() as i64;
+        let mut anomalies = vec![];
+        for event in &events {
+            let expected = AccountAddress::from_str(&event.address).ok()
+                .and_then(|address| tree.get(address.as_h160()))
+                .map(|(_, leaf)| BigDecimal::from_str(&leaf.amount))
+                .transpose()?;
+            let claimed = BigDecimal::from_str(&event.amount.0.to_string())?;
+            let claimed_before = first_claims.iter()
+                .find(|first| first.address == event.address)
+                .map(|first| first.tx_hash != event.tx_hash || first.log_index != event.log_index)
+                .unwrap_or(false);
+            for kind in check_claim(expected.as_ref(), &claimed, claimed_before) {
+                log::warn!("claim anomaly {:?} in tx {} log {} of {}", kind, event.tx_hash, event.log_index, event.address);
+                anomalies.push(ClaimAnomaly {
+                    tx_hash: event.tx_hash.clone(),
+                    log_index: event.log_index,
+                    kind: kind.as_ref().to_string(),
+                    block_number: event.block_number,
+                    address: event.address.clone(),
+                    claimed_amount: event.amount.clone(),
+                    expected_amount: expected.as_ref().map(|e| Decimal::from_str(&e.to_string()).unwrap()),
+                    snapshot_version: tree.version(),
+                    detected_at,
+                });
+            }
+        }
+        db::save_claim_anomalies(rb, anomalies).await?;
+        db::set_claim_events_checked(rb).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_check_claim() {
+        let amount = BigDecimal::from(100);
+        assert!(check_claim(Some(&amount), &amount, false).is_empty());
+        assert_eq!(check_claim(None, &amount, false), vec![AnomalyKind::UnknownAddress]);
+        assert_eq!(check_claim(Some(&BigDecimal::from(99)), &amount, false), vec![AnomalyKind::WrongAmount]);
+        assert_eq!(check_claim(Some(&amount), &amount, true), vec![AnomalyKind::DoubleClaim]);
+        assert_eq!(check_claim(None, &amount, true), vec![AnomalyKind::UnknownAddress, AnomalyKind::DoubleClaim]);
+    }
+}
@@ -1,28 +1,236 @@
-use ethabi::{decode,Address, ParamType, Uint};
-use web3::types::{H160, Log};
+use anyhow::format_err;
+use ethabi::{Address, Event, EventParam, ParamType, RawLog, Token, Uint};
+use web3::types::{H256, Log};
+
+/// Where a log is in the chain, known for the logs of mined blocks.
+#[derive(Debug, Clone)]
+pub struct LogPosition {
+    pub tx_hash: H256,
+    pub log_index: u64,
+    pub block_number: u64,
+    pub block_hash: H256,
+}
+
+impl TryFrom<&Log> for LogPosition {
+    type Error = anyhow::Error;
+
+    fn try_from(log: &Log) -> Result<Self, Self::Error> {
+        // only logs of mined blocks are requested, they always carry their position
+        let (Some(tx_hash), Some(log_index), Some(block_number), Some(block_hash)) =
+            (log.transaction_hash, log.log_index, log.block_number, log.block_hash) else {
+            return Err(format_err!("log without its position in the chain: {:?}", log));
+        };
+        Ok(LogPosition {
+            tx_hash,
+            log_index: log_index.as_u64(),
+            block_number: block_number.as_u64(),
+            block_hash,
+        })
+    }
+}
+
+/// Decodes the parameters of `log` as an `abi` event.
+pub fn decode_log(abi: &Event, log: &Log) -> anyhow::Result<Vec<ethabi::LogParam>> {
+    let log = abi.parse_log(RawLog {
+        topics: log.topics.clone(),
+        data: log.data.0.clone(),
+    })?;
+    Ok(log.params)
+}
+
+fn param(name: &str, kind: ParamType, indexed: bool) -> EventParam {
+    EventParam {
+        name: name.to_string(),
+        kind,
+        indexed,
+    }
+}
+
+/// `Claimed(address indexed account, uint256 amount, uint256 claimed_time)` of the distributor.
+pub fn claimed_abi() -> Event {
+    Event {
+        name: "Claimed".to_string(),
+        inputs: vec![
+            param("account", ParamType::Address, true),
+            param("amount", ParamType::Uint(256), false),
+            param("claimed_time", ParamType::Uint(256), false),
+        ],
+        anonymous: false,
+    }
+}
+
+/// `MerkleRootUpdated(bytes32 merkleRoot)` of the distributor.
+pub fn merkle_root_updated_abi() -> Event {
+    Event {
+        name: "MerkleRootUpdated".to_string(),
+        inputs: vec![
+            param("merkleRoot", ParamType::FixedBytes(32), false),
+        ],
+        anonymous: false,
+    }
+}
+
+/// `Swept(address indexed to, uint256 amount)` of the distributor, emitted when unclaimed tokens are recovered.
+pub fn swept_abi() -> Event {
+    Event {
+        name: "Swept".to_string(),
+        inputs: vec![
+            param("to", ParamType::Address, true),
+            param("amount", ParamType::Uint(256), false),
+        ],
+        anonymous: false,
+    }
+}
+
+/// `Transfer(address indexed from, address indexed to, uint256 value)` of the ERC-20 token.
+pub fn transfer_abi() -> Event {
+    Event {
+        name: "Transfer".to_string(),
+        inputs: vec![
+            param("from", ParamType::Address, true),
+            param("to", ParamType::Address, true),
+            param("value", ParamType::Uint(256), false),
+        ],
+        anonymous: false,
+    }
+}
+
+/// The JSON form of a decoded value: addresses, bytes and integers as strings.
+pub fn token_to_json(token: &Token) -> serde_json::Value {
+    match token {
+        Token::Address(address) => serde_json::Value::String(format!("{:?}", address)),
+        Token::FixedBytes(bytes) | Token::Bytes(bytes) => serde_json::Value::String(format!("0x{}", hex::encode(bytes))),
+        Token::Int(value) | Token::Uint(value) => serde_json::Value::String(value.to_string()),
+        Token::Bool(value) => serde_json::Value::Bool(*value),
+        Token::String(value) => serde_json::Value::String(value.clone()),
+        Token::FixedArray(tokens) | Token::Array(tokens) | Token::Tuple(tokens) =>
+            serde_json::Value::Array(tokens.iter().map(token_to_json).collect()),
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct ClaimEvent {
     pub address: Address,
     pub amount: Uint,
     pub claimed_time: Uint,
+    pub tx_hash: H256,
+    pub log_index: u64,
+    pub block_number: u64,
+    pub block_hash: H256,
 }
 
 impl TryFrom<Log> for ClaimEvent {
-    type Error = ethabi::Error;
+    type Error = anyhow::Error;
 
     fn try_from(event: Log) -> Result<Self, Self::Error> {
-        let dec_ev = decode(
-            &[
-                ParamType::Uint(256),
-                ParamType::Uint(256),
-            ],
-            &event.data.0,
-        )?;
+        let position = LogPosition::try_from(&event)?;
+        let params = decode_log(&claimed_abi(), &event)?;
+        let values = params.iter().map(|p| p.value.clone()).collect::<Vec<_>>();
+        let [Token::Address(address), Token::Uint(amount), Token::Uint(claimed_time)] = values.as_slice() else {
+            return Err(format_err!("unexpected Claimed params: {:?}", params));
+        };
         Ok(ClaimEvent {
-            address: H160::from_slice(&event.topics[1].as_bytes()[12..]),
-            amount: dec_ev[0].clone().into_uint().unwrap(),
-            claimed_time: dec_ev[1].clone().into_uint().unwrap(),
+            address: *address,
+            amount: *amount,
+            claimed_time: *claimed_time,
+            tx_hash: position.tx_hash,
+            log_index: position.log_index,
+            block_number: position.block_number,
+            block_hash: position.block_hash,
         })
     }
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Clone)]
+pub struct TransferEvent {
+    pub from: Address,
+    pub to: Address,
+    pub value: Uint,
+    pub position: LogPosition,
+}
+
+impl TryFrom<Log> for TransferEvent {
+    type Error = anyhow::Error;
+
+    fn try_from(event: Log) -> Result<Self, Self::Error> {
+        let position = LogPosition::try_from(&event)?;
+        let params = decode_log(&transfer_abi(), &event)?;
+        let values = params.iter().map(|p| p.value.clone()).collect::<Vec<_>>();
+        let [Token::Address(from), Token::Address(to), Token::Uint(value)] = values.as_slice() else {
+            return Err(format_err!("unexpected Transfer params: {:?}", params));
+        };
+        Ok(TransferEvent {
+            from: *from,
+            to: *to,
+            value: *value,
+            position,
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use web3::types::{Bytes, H160, U256};
+    use super::*;
+
+    /// A log of a mined block, `data` being the ABI encoding of `tokens`.
+    pub(crate) fn log(address: H160, topics: Vec<H256>, tokens: &[Token], block_number: u64, log_index: u64) -> Log {
+        Log {
+            address,
+            topics,
+            data: Bytes(ethabi::encode(tokens)),
+            block_hash: Some(H256::from_low_u64_be(block_number)),
+            block_number: Some(block_number.into()),
+            transaction_hash: Some(H256::from_low_u64_be(block_number * 10 + log_index)),
+            transaction_index: Some(0.into()),
+            log_index: Some(log_index.into()),
+            transaction_log_index: None,
+            log_type: None,
+            removed: Some(false),
+        }
+    }
+
+    fn claimed(account: H160, amount: u64) -> Log {
+        log(H160::repeat_byte(0x55), vec![claimed_abi().signature(), H256::from(account)],
+            &[Token::Uint(U256::from(amount)), Token::Uint(U256::from(1_700_000_000u64))], 42, 3)
+    }
+
+    #[test]
+    fn test_token_to_json() {
+        let address = H160::repeat_byte(0xab);
+        assert_eq!(token_to_json(&Token::Address(address)), serde_json::json!("0xabababababababababababababababababababab"));
+        assert_eq!(token_to_json(&Token::FixedBytes(vec![0x01, 0xff])), serde_json::json!("0x01ff"));
+        assert_eq!(token_to_json(&Token::Bytes(vec![])), serde_json::json!("0x"));
+        // integers wider than JSON numbers are kept exact
+        assert_eq!(token_to_json(&Token::Uint(U256::MAX)), serde_json::json!(U256::MAX.to_string()));
+        assert_eq!(token_to_json(&Token::Bool(true)), serde_json::json!(true));
+        assert_eq!(token_to_json(&Token::String("a".to_string())), serde_json::json!("a"));
+        assert_eq!(token_to_json(&Token::Tuple(vec![Token::Uint(U256::from(1)), Token::Array(vec![Token::Bool(false)])])),
+                   serde_json::json!(["1", [false]]));
+    }
+
+    #[test]
+    fn test_decode_log() {
+        let account = H160::repeat_byte(0x12);
+        let params = decode_log(&claimed_abi(), &claimed(account, 7)).unwrap();
+        let params = params.into_iter().map(|p| (p.name, p.value)).collect::<Vec<_>>();
+        assert_eq!(params, vec![
+            ("account".to_string(), Token::Address(account)),
+            ("amount".to_string(), Token::Uint(U256::from(7))),
+            ("claimed_time".to_string(), Token::Uint(U256::from(1_700_000_000u64))),
+        ]);
+        // a log of another event does not decode
+        assert!(decode_log(&transfer_abi(), &claimed(account, 7)).is_err());
+    }
+
+    #[test]
+    fn test_claim_event() {
+        let account = H160::repeat_byte(0x12);
+        let event = ClaimEvent::try_from(claimed(account, 7)).unwrap();
+        assert_eq!(event.address, account);
+        assert_eq!(event.amount, U256::from(7));
+        assert_eq!((event.block_number, event.log_index), (42, 3));
+        let pending = Log { log_index: None, ..claimed(account, 7) };
+        assert!(ClaimEvent::try_from(pending).is_err());
+    }
+}
@@ -1,2 +1,6 @@
+pub mod claim_check;
 pub mod event;
+pub mod registry;
+pub mod rpc;
+#[allow(clippy::module_inception)]
 pub mod watcher;
\ No newline at end of file
@@ -0,0 +1,333 @@
+use std::sync::Arc;
+use std::str::FromStr;
+use async_trait::async_trait;
+use ethabi::Event;
+use rbatis::executor::Executor;
+use rbatis::RBatis;
+use web3::types::{H160, H256, Log};
+use crate::config::Config;
+use crate::db;
+use crate::db::tables::{ClaimEventRecord, DistributorEvent, TokenTransfer};
+use crate::watcher::claim_check::ClaimChecker;
+use crate::watcher::event::{claimed_abi, ClaimEvent, decode_log, LogPosition, merkle_root_updated_abi, swept_abi, token_to_json, transfer_abi, TransferEvent};
+
+/// An event the watcher indexes: which logs are its own, what they are decoded into and how they are stored.
+#[async_trait]
+pub trait EventHandler: Send + Sync {
+    /// What a log of the event is decoded into.
+    type Event: Send;
+
+    /// ABI of the event, its signature is the topic its logs are requested with.
+    fn abi(&self) -> &Event;
+
+    /// The contract emitting the event.
+    fn contract(&self) -> H160;
+
+    /// Decodes a log of the event, logs of mined blocks only.
+    fn decode(&self, log: Log) -> anyhow::Result<Self::Event>;
+
+    /// Saves the events of a synced range, in chain order, in the transaction of that range.
+    async fn persist(&self, rb: &dyn Executor, events: Vec<Self::Event>) -> anyhow::Result<()>;
+
+    /// Forgets what was saved from blocks after `block_number`, after a reorg.
+    async fn rollback(&self, rb: &dyn Executor, block_number: i64) -> anyhow::Result<()>;
+}
+
+/// An [`EventHandler`] whatever its event type, as the registry holds them.
+#[async_trait]
+trait LogHandler: Send + Sync {
+    /// Decodes the logs, all of them before saving any, and saves them.
+    async fn persist_logs(&self, rb: &dyn Executor, logs: Vec<Log>) -> anyhow::Result<()>;
+
+    async fn rollback(&self, rb: &dyn Executor, block_number: i64) -> anyhow::Result<()>;
+}
+
+#[async_trait]
+impl<H: EventHandler> LogHandler for H {
+    async fn persist_logs(&self, rb: &dyn Executor, logs: Vec<Log>) -> anyhow::Result<()> {
+        let events = logs.into_iter()
+            .map(|log| self.decode(log))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        self.persist(rb, events).await
+    }
+
+    async fn rollback(&self, rb: &dyn Executor, block_number: i64) -> anyhow::Result<()> {
+        EventHandler::rollback(self, rb, block_number).await
+    }
+}
+
+/// `Claimed` logs, saved as claim events which the claimed accounts are derived from, and checked
+/// against the eligible tree.
+pub struct ClaimedHandler {
+    abi: Event,
+    contract: H160,
+    checker: ClaimChecker,
+}
+
+impl ClaimedHandler {
+    pub fn new(contract: H160, checker: ClaimChecker) -> Self {
+        Self { abi: claimed_abi(), contract, checker }
+    }
+}
+
+#[async_trait]
+impl EventHandler for ClaimedHandler {
+    type Event = ClaimEventRecord;
+
+    fn abi(&self) -> &Event {
+        &self.abi
+    }
+
+    fn contract(&self) -> H160 {
+        self.contract
+    }
+
+    fn decode(&self, log: Log) -> anyhow::Result<ClaimEventRecord> {
+        Ok(ClaimEventRecord::from(ClaimEvent::try_from(log)?))
+    }
+
+    async fn persist(&self, rb: &dyn Executor, events: Vec<ClaimEventRecord>) -> anyhow::Result<()> {
+        db::save_claim_events(rb, events).await?;
+        self.checker.check(rb).await
+    }
+
+    async fn rollback(&self, rb: &dyn Executor, block_number: i64) -> anyhow::Result<()> {
+        db::rollback_claim_anomalies(rb, block_number).await?;
+        db::rollback_claim_events(rb, block_number).await
+    }
+}
+
+/// Logs saved as they are in `distributor_events`, with their parameters as JSON.
+pub struct DistributorEventHandler {
+    abi: Event,
+    contract: H160,
+}
+
+impl DistributorEventHandler {
+    pub fn new(abi: Event, contract: H160) -> Self {
+        Self { abi, contract }
+    }
+}
+
+#[async_trait]
+impl EventHandler for DistributorEventHandler {
+    type Event = DistributorEvent;
+
+    fn abi(&self) -> &Event {
+        &self.abi
+    }
+
+    fn contract(&self) -> H160 {
+        self.contract
+    }
+
+    fn decode(&self, log: Log) -> anyhow::Result<DistributorEvent> {
+        let position = LogPosition::try_from(&log)?;
+        let params = decode_log(&self.abi, &log)?.into_iter()
+            .map(|p| (p.name, token_to_json(&p.value)))
+            .collect::<serde_json::Map<_, _>>();
+        Ok(DistributorEvent {
+            tx_hash: format!("{:?}", position.tx_hash),
+            log_index: position.log_index as i64,
+            block_number: position.block_number as i64,
+            block_hash: format!("{:?}", position.block_hash),
+            event_name: self.abi.name.clone(),
+            params: serde_json::Value::Object(params).to_string(),
+        })
+    }
+
+    async fn persist(&self, rb: &dyn Executor, events: Vec<DistributorEvent>) -> anyhow::Result<()> {
+        db::save_distributor_events(rb, events).await
+    }
+
+    async fn rollback(&self, rb: &dyn Executor, block_number: i64) -> anyhow::Result<()> {
+        db::rollback_distributor_events(rb, &self.abi.name, block_number).await
+    }
+}
+
+/// `Transfer` logs of the token, saved as transfers which the token balances are derived from.
+pub struct TransferHandler {
+    abi: Event,
+    contract: H160,
+}
+
+impl TransferHandler {
+    pub fn new(contract: H160) -> Self {
+        Self { abi: transfer_abi(), contract }
+    }
+}
+
+#[async_trait]
+impl EventHandler for TransferHandler {
+    type Event = TokenTransfer;
+
+    fn abi(&self) -> &Event {
+        &self.abi
+    }
+
+    fn contract(&self) -> H160 {
+        self.contract
+    }
+
+    fn decode(&self, log: Log) -> anyhow::Result<TokenTransfer> {
+        Ok(TokenTransfer::from(TransferEvent::try_from(log)?))
+    }
+
+    async fn persist(&self, rb: &dyn Executor, transfers: Vec<TokenTransfer>) -> anyhow::Result<()> {
+        db::save_token_transfers(rb, transfers).await
+    }
+
+    async fn rollback(&self, rb: &dyn Executor, block_number: i64) -> anyhow::Result<()> {
+        db::rollback_token_transfers(rb, block_number).await
+    }
+}
+
+/// A registered handler with the contract and topic of its logs.
+#[derive(Clone)]
+struct RegisteredHandler {
+    contract: H160,
+    topic: H256,
+    handler: Arc<dyn LogHandler>,
+}
+
+/// Every indexed event. Their logs are requested together and each log is handed to the handler
+/// of its contract and topic.
+#[derive(Clone, Default)]
+pub struct EventRegistry {
+    handlers: Vec<RegisteredHandler>,
+}
+
+impl EventRegistry {
+    /// The distributor events: `Claimed`, `MerkleRootUpdated` and `Swept`, and the token `Transfer`
+    /// unless `INDEX_TOKEN_TRANSFERS` is false. Claims are checked against the tree of the active snapshot.
+    pub fn from_config(config: &Config, db: RBatis) -> anyhow::Result<Self> {
+        let checker = ClaimChecker::new(db, config.merkle_snapshot_version);
+        let config = &config.watcher;
+        let distributor = H160::from_str(&config.contract_address)?;
+        let mut registry = Self::default();
+        registry.register(ClaimedHandler::new(distributor, checker));
+        registry.register(DistributorEventHandler::new(merkle_root_updated_abi(), distributor));
+        registry.register(DistributorEventHandler::new(swept_abi(), distributor));
+        if config.index_token_transfers {
+            registry.register(TransferHandler::new(H160::from_str(&config.token_address)?));
+        }
+        Ok(registry)
+    }
+
+    pub fn register(&mut self, handler: impl EventHandler + 'static) {
+        self.handlers.push(RegisteredHandler {
+            contract: handler.contract(),
+            topic: handler.abi().signature(),
+            handler: Arc::new(handler),
+        });
+    }
+
+    /// The contracts to request logs from.
+    pub fn addresses(&self) -> Vec<H160> {
+        let mut addresses = self.handlers.iter().map(|h| h.contract).collect::<Vec<_>>();
+        addresses.sort();
+        addresses.dedup();
+        addresses
+    }
+
+    /// The event signatures to request logs of.
+    pub fn topics(&self) -> Vec<H256> {
+        let mut topics = self.handlers.iter().map(|h| h.topic).collect::<Vec<_>>();
+        topics.sort();
+        topics.dedup();
+        topics
+    }
+
+    /// Splits the logs among the handlers, in the order they were registered, each handler keeps the
+    /// order of its logs. Logs no handler registered are skipped.
+    fn dispatch(&self, logs: Vec<Log>) -> Vec<Vec<Log>> {
+        let mut handled = vec![vec![]; self.handlers.len()];
+        for log in logs {
+            let handler = self.handlers.iter().position(|h| {
+                log.address == h.contract && log.topics.first() == Some(&h.topic)
+            });
+            match handler {
+                Some(i) => handled[i].push(log),
+                None => log::warn!("no handler for log {:?}", log),
+            }
+        }
+        handled
+    }
+
+    /// Hands the logs of a synced range, in chain order, to their handlers one handler after the
+    /// other: every handler sees its own logs in chain order, not interleaved with the others'.
+    pub async fn persist(&self, rb: &dyn Executor, logs: Vec<Log>) -> anyhow::Result<()> {
+        for (registered, logs) in self.handlers.iter().zip(self.dispatch(logs)) {
+            if !logs.is_empty() {
+                registered.handler.persist_logs(rb, logs).await?;
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn rollback(&self, rb: &dyn Executor, block_number: i64) -> anyhow::Result<()> {
+        for registered in &self.handlers {
+            registered.handler.rollback(rb, block_number).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ethabi::Token;
+    use web3::types::U256;
+    use crate::watcher::event::test::log;
+    use super::*;
+
+    fn address(byte: u8) -> H160 {
+        H160::repeat_byte(byte)
+    }
+
+    fn topic(address: H160) -> H256 {
+        H256::from(address)
+    }
+
+    #[test]
+    fn test_dispatch() {
+        let distributor = address(0x55);
+        let token = address(0x66);
+        let mut registry = EventRegistry::default();
+        registry.register(DistributorEventHandler::new(merkle_root_updated_abi(), distributor));
+        registry.register(DistributorEventHandler::new(swept_abi(), distributor));
+        registry.register(TransferHandler::new(token));
+        assert_eq!(registry.addresses(), vec![distributor, token]);
+        assert_eq!(registry.topics().len(), 3);
+
+        let swept = |block| log(distributor, vec![swept_abi().signature(), topic(address(1))],
+                                &[Token::Uint(U256::from(3))], block, 0);
+        let transfer = |contract, block| log(contract, vec![transfer_abi().signature(), topic(address(1)), topic(address(2))],
+                                             &[Token::Uint(U256::from(7))], block, 1);
+        let root = log(distributor, vec![merkle_root_updated_abi().signature()], &[Token::FixedBytes(vec![0x11; 32])], 3, 2);
+        let logs = vec![swept(1), transfer(token, 1), transfer(distributor, 2), swept(2), root.clone()];
+
+        let handled = registry.dispatch(logs);
+        assert_eq!(handled, vec![vec![root], vec![swept(1), swept(2)], vec![transfer(token, 1)]]);
+    }
+
+    #[test]
+    fn test_decode() {
+        let handler = DistributorEventHandler::new(swept_abi(), address(0x55));
+        let swept = log(address(0x55), vec![swept_abi().signature(), topic(address(1))], &[Token::Uint(U256::from(3))], 9, 4);
+        let event = handler.decode(swept.clone()).unwrap();
+        assert_eq!(event.event_name, "Swept");
+        assert_eq!(event.block_number, 9);
+        assert_eq!(event.log_index, 4);
+        let params: serde_json::Value = serde_json::from_str(&event.params).unwrap();
+        assert_eq!(params, serde_json::json!({"to": format!("{:?}", address(1)), "amount": "3"}));
+
+        let transfer = TransferHandler::new(address(0x66))
+            .decode(log(address(0x66), vec![transfer_abi().signature(), topic(address(1)), topic(address(2))],
+                        &[Token::Uint(U256::from(7))], 9, 5))
+            .unwrap();
+        assert_eq!((transfer.from_address, transfer.to_address), (format!("{:?}", address(1)), format!("{:?}", address(2))));
+
+        let pending = Log { block_number: None, ..swept };
+        assert!(handler.decode(pending).is_err());
+    }
+}
@@ -0,0 +1,131 @@
+use std::future::Future;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+use anyhow::format_err;
+use web3::transports::Http;
+use web3::Web3;
+use tokio::sync::Semaphore;
+use crate::config::WatcherConfig;
+
+/// Score of a healthy endpoint, halved on each failure and raised back on each success.
+const MAX_SCORE: u32 = 100;
+const SUCCESS_SCORE_GAIN: u32 = 10;
+/// Upper bound of the delay between two attempts.
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Messages providers answer `eth_getLogs` with when the block range or the result is too large.
+const LOG_RANGE_ERRORS: [&str; 6] = [
+    "query returned more than",
+    "too many results",
+    "range too large",
+    "range is too large",
+    "response size exceeded",
+    "exceed maximum",
+];
+
+/// The provider refused an `eth_getLogs` request because of its range, a smaller range should be requested.
+#[derive(Debug)]
+pub struct LogRangeTooLarge(pub String);
+
+impl std::fmt::Display for LogRangeTooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "log range too large: {}", self.0)
+    }
+}
+
+impl std::error::Error for LogRangeTooLarge {}
+
+fn is_log_range_error(error: &web3::Error) -> bool {
+    match error {
+        web3::Error::Rpc(e) => {
+            let message = e.message.to_lowercase();
+            LOG_RANGE_ERRORS.iter().any(|m| message.contains(m))
+        },
+        _ => false,
+    }
+}
+
+struct Endpoint {
+    url: String,
+    web3: Web3<Http>,
+    score: AtomicU32,
+}
+
+/// The RPC endpoints the watcher reads the chain from. Each request goes to the healthiest endpoint
+/// and is retried with an exponential backoff, which moves it to another endpoint once the failing
+/// one's score drops below theirs.
+#[derive(Clone)]
+pub struct RpcPool {
+    endpoints: Arc<Vec<Endpoint>>,
+    permits: Arc<Semaphore>,
+    request_timeout: Duration,
+    max_retries: u32,
+    backoff: Duration,
+}
+
+impl RpcPool {
+    pub fn new(config: &WatcherConfig) -> anyhow::Result<Self> {
+        let endpoints = config.remote_web3_urls.iter()
+            .map(|url| {
+                let transport = Http::new(url)
+                    .map_err(|e| format_err!("invalid RPC endpoint {},{:?}", url, e))?;
+                Ok(Endpoint {
+                    url: url.clone(),
+                    web3: Web3::new(transport),
+                    score: AtomicU32::new(MAX_SCORE),
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(Self {
+            endpoints: Arc::new(endpoints),
+            permits: Arc::new(Semaphore::new(config.rpc_max_concurrent_requests)),
+            request_timeout: Duration::from_secs(config.rpc_timeout_secs),
+            max_retries: config.rpc_max_retries,
+            backoff: Duration::from_millis(config.rpc_backoff_ms),
+        })
+    }
+
+    /// The endpoint with the highest score, the first configured one among equals.
+    fn healthiest(&self) -> &Endpoint {
+        self.endpoints.iter()
+            .rev()
+            .max_by_key(|e| e.score.load(Ordering::Relaxed))
+            .expect("the pool has at least one endpoint")
+    }
+
+    /// Runs `request` on the healthiest endpoint, retrying on errors and timeouts up to `RPC_MAX_RETRIES` times.
+    /// Range errors are not retried and returned as [`LogRangeTooLarge`].
+    pub async fn call<T, F, Fut>(&self, name: &str, request: F) -> anyhow::Result<T>
+        where
+            F: Fn(Web3<Http>) -> Fut,
+            Fut: Future<Output = web3::Result<T>>,
+    {
+        let mut attempt = 0;
+        loop {
+            let permit = self.permits.acquire().await.expect("the semaphore is never closed");
+            let endpoint = self.healthiest();
+            let response = tokio::time::timeout(self.request_timeout, request(endpoint.web3.clone())).await;
+            drop(permit);
+            let error = match response {
+                Ok(Ok(value)) => {
+                    let score = endpoint.score.load(Ordering::Relaxed);
+                    endpoint.score.store(MAX_SCORE.min(score + SUCCESS_SCORE_GAIN), Ordering::Relaxed);
+                    return Ok(value);
+                },
+                Ok(Err(e)) if is_log_range_error(&e) => return Err(LogRangeTooLarge(format!("{:?}", e)).into()),
+                Ok(Err(e)) => format_err!("{:?}", e),
+                Err(_) => format_err!("timed out after {:?}", self.request_timeout),
+            };
+            let score = endpoint.score.load(Ordering::Relaxed);
+            endpoint.score.store(score / 2, Ordering::Relaxed);
+            if attempt >= self.max_retries {
+                return Err(format_err!("{} failed on {} after {} attempts,{}", name, endpoint.url, attempt + 1, error));
+            }
+            let delay = MAX_BACKOFF.min(self.backoff * 2u32.saturating_pow(attempt));
+            log::warn!("{} failed on {},{}, retrying in {:?}", name, endpoint.url, error, delay);
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
@@ -1,146 +1,292 @@
 use std::cmp;
-use std::fmt::Debug;
 use std::str::FromStr;
 use std::time::Duration;
 use anyhow::format_err;
 use tokio::task::JoinHandle;
-use web3::transports::Http;
-use web3::types::{BlockNumber, FilterBuilder, H160, H256, Log};
+use futures::StreamExt;
+use web3::api::SubscriptionStream;
+use web3::transports::WebSocket;
+use serde::de::DeserializeOwned;
+use web3::types::{BlockHeader, BlockId, BlockNumber, FilterBuilder, H160, H256, Log};
 use web3::Web3;
+use crate::campaign::CampaignState;
 use crate::config::Config;
 use crate::db;
-use crate::db::tables::ClaimedAccount;
-use crate::watcher::event::ClaimEvent;
+use crate::db::tables::{ClaimEventRecord, SyncedBlock};
+use crate::watcher::event::{claimed_abi, ClaimEvent};
+use crate::watcher::registry::EventRegistry;
+use crate::watcher::rpc::{LogRangeTooLarge, RpcPool};
+
+/// How many synced block hashes are kept to find the common ancestor after a reorg.
+const KEPT_SYNCED_BLOCKS: i64 = 128;
+/// The step doubles after a whole step returned fewer logs than this.
+const SMALL_RESPONSE_LOGS: usize = 1000;
 
 #[derive(Clone)]
 pub struct ChainWatcher {
     pub config: Config,
-    pub web3: Web3<Http>,
+    pub rpc: RpcPool,
     pub db: rbatis::RBatis,
+    pub campaign: CampaignState,
+    pub registry: EventRegistry,
+    /// Blocks per `eth_getLogs` request, between `SYNC_MIN_STEP` and `SYNC_MAX_STEP`.
+    pub sync_step: u64,
 }
 impl ChainWatcher {
-    pub async fn new(config:Config,db: rbatis::RBatis) -> anyhow::Result<Self> {
-        let transport = Http::new(&config.remote_web3_url).unwrap();
-        let web3 = Web3::new(transport);
+    pub async fn new(config:Config,db: rbatis::RBatis,campaign: CampaignState) -> anyhow::Result<Self> {
+        config.watcher.validate()?;
+        let rpc = RpcPool::new(&config.watcher)?;
+        let sync_step = config.watcher.sync_step;
+        let registry = EventRegistry::from_config(&config, db.clone())?;
         Ok(Self {
-            web3,
+            rpc,
+            registry,
+            sync_step,
             config,
             db,
+            campaign,
         })
     }
-    async fn sync_claim_events(
+    /// Saves the logs of a block range and moves the sync checkpoint to its end in one transaction.
+    /// The unconfirmed claims of the range are replaced by the ones of its logs.
+    async fn save_synced_range(&self, logs: Vec<Log>, end_block: SyncedBlock) -> anyhow::Result<()> {
+        let tx = self.db.acquire_begin().await?;
+        let ret = async {
+            db::discard_unconfirmed_claim_events(&tx, end_block.block_number).await?;
+            self.registry.persist(&tx, logs).await?;
+            db::upsert_last_sync_block(&tx, end_block.block_number).await?;
+            db::save_synced_block(&tx, end_block, KEPT_SYNCED_BLOCKS).await
+        }.await;
+        db::finish_transaction(tx, ret).await
+    }
+    /// Returns the logs of every registered event between `from` and `to`, in chain order.
+    async fn sync_events(
         &mut self,
         from: u64,
         to: u64,
-    ) -> anyhow::Result<()> {
-        let abi_string = r#"[ {
-            "anonymous": false,
-            "inputs": [
-                {
-                    "indexed": true,
-                    "internalType": "address",
-                    "name": "account",
-                    "type": "address"
+    ) -> anyhow::Result<Vec<Log>> {
+        let address = self.registry.addresses();
+        let topics = self.registry.topics();
+        log::info!("sync events from:{} to:{}",from,to);
+        // ranges the provider refuses are bisected, the lower half is requested first
+        let mut ranges = vec![(from, to)];
+        let mut logs = vec![];
+        let mut split = false;
+        while let Some((from, to)) = ranges.pop() {
+            let filter = FilterBuilder::default()
+                .address(address.clone())
+                .from_block(BlockNumber::Number(from.into()))
+                .to_block(BlockNumber::Number(to.into()))
+                .topics(Some(topics.clone()), None, None, None)
+                .build();
+            let range_size = to - from + 1;
+            match self.rpc.call("eth_getLogs", |web3| web3.eth().logs(filter.clone())).await {
+                Ok(range_logs) => {
+                    if !split && range_size >= self.sync_step && range_logs.len() < SMALL_RESPONSE_LOGS {
+                        self.sync_step = cmp::min(self.sync_step * 2, self.config.watcher.sync_max_step);
+                    }
+                    logs.extend(range_logs);
                 },
-                {
-                    "indexed": false,
-                    "internalType": "uint256",
-                    "name": "amount",
-                    "type": "uint256"
+                Err(e) if e.is::<LogRangeTooLarge>() && range_size > self.config.watcher.sync_min_step => {
+                    let middle = from + range_size / 2;
+                    log::info!("{}, splitting blocks {}-{} at {}", e, from, to, middle);
+                    self.sync_step = cmp::max(range_size / 2, self.config.watcher.sync_min_step);
+                    split = true;
+                    ranges.push((middle, to));
+                    ranges.push((from, middle - 1));
                 },
-                {
-                    "indexed": false,
-                    "internalType": "uint256",
-                    "name": "claimed_time",
-                    "type": "uint256"
-                }
-            ],
-            "name": "Claimed",
-            "type": "event"
-        }]"#;
-        let token_contract = ethabi::Contract::load(abi_string.as_bytes()).unwrap();
-        let topic = token_contract
-            .event("Claimed")
-            .expect("token contract abi error")
-            .signature();
-        let token_address = H160::from_str(&self.config.token_address.clone()).unwrap();
-        let logs: Vec<ClaimEvent> = self.sync_events(from,to, vec![token_address], vec![topic]).await?;
-        if !logs.is_empty() {
-            let accounts = logs.iter().map(|l| (*l).clone().into())
-                .collect::<Vec<ClaimedAccount>>();
-            db::save_claimed_accounts(&mut self.db, accounts).await?;
+                Err(e) => return Err(e),
+            }
         }
-        Ok(())
-    }
-    async fn sync_events<T>(
-        &mut self,
-        from: u64,
-        to: u64,
-        address: Vec<H160>,
-        topics: Vec<H256>
-    ) -> anyhow::Result<Vec<T>>
-        where
-            T: TryFrom<Log>,
-            T::Error: Debug,
-    {
-        log::info!("sync events from:{} to:{}",from,to);
-        let filter = FilterBuilder::default()
-            .address(address)
-            .from_block(BlockNumber::Number(from.into()))
-            .to_block(BlockNumber::Number(to.into()))
-            .topics(Some(topics), None, None, None)
-            .build();
-        let mut logs = self.web3.eth().logs(filter).await?;
-        println!("logs is {:?}",logs);
-        let is_possible_to_sort_logs = logs.iter().all(|log| log.log_index.is_some());
+        log::debug!("logs is {:?}", logs);
+        let is_possible_to_sort_logs = logs.iter().all(|log| log.block_number.is_some() && log.log_index.is_some());
         if is_possible_to_sort_logs {
             logs.sort_by_key(|log| {
-                log.log_index
-                    .expect("all logs log_index should have values")
+                (log.block_number.expect("all logs block_number should have values"),
+                 log.log_index.expect("all logs log_index should have values"))
             });
         } else {
             log::warn!("Some of the log entries does not have log_index, we rely on the provided logs order");
         }
+        Ok(logs)
+    }
 
+    async fn block_hash(&self, number: u64) -> anyhow::Result<Option<H256>> {
+        let block = self.rpc
+            .call("eth_getBlockByNumber", |web3| web3.eth().block(BlockId::Number(BlockNumber::Number(number.into()))))
+            .await?;
+        Ok(block.and_then(|b| b.hash))
+    }
 
-        logs.into_iter()
-            .map(|event| {
-                T::try_from(event)
-                    .map_err(|e| format_err!("Failed to parse event log from ETH: {:?}", e))
-            })
-            .collect()
+    /// Checks the latest synced block is still on the chain. If it is not, rolls back to the latest
+    /// synced block which still is, or to the start block if none is, and returns the new checkpoint.
+    async fn handle_reorg(&mut self, last_synced_block: u64) -> anyhow::Result<u64> {
+        let synced_blocks = db::get_synced_blocks(&self.db).await?;
+        let mut ancestor = None;
+        for block in &synced_blocks {
+            let hash = self.block_hash(block.block_number as u64).await?;
+            if hash.map(|h| format!("{:?}", h)).as_ref() == Some(&block.block_hash) {
+                ancestor = Some(block.block_number as u64);
+                break;
+            }
+        }
+        match (synced_blocks.first(), ancestor) {
+            (None, _) => return Ok(last_synced_block),
+            (Some(latest), Some(ancestor)) if latest.block_number as u64 == ancestor => return Ok(last_synced_block),
+            _ => {},
+        }
+        let ancestor = ancestor.unwrap_or_else(|| {
+            log::warn!("no synced block hash matches the chain, syncing again from the start block");
+            self.config.watcher.sync_start_block
+        });
+        log::warn!("chain reorganized below block {}, rolling back to block {}", last_synced_block, ancestor);
+        let tx = self.db.acquire_begin().await?;
+        let ret = async {
+            self.registry.rollback(&tx, ancestor as i64).await?;
+            db::rollback_sync_checkpoint(&tx, ancestor as i64).await
+        }.await;
+        db::finish_transaction(tx, ret).await?;
+        Ok(ancestor)
     }
 
-    async fn run_sync_events(&mut self) ->anyhow::Result<()> {
-        let last_synced_block = db::get_last_sync_block(&self.db,self.config.sync_start_block).await?;
-        let chain_block_number = self.web3.eth().block_number().await?.as_u64();
-        let sync_step = 1000u64;
+    /// Syncs the registered events again from `from_block` up to the last synced block, or to `to_block`
+    /// if it is lower, for the events registered after their blocks were synced. Events already saved
+    /// are kept and the sync checkpoint is left where it is.
+    pub async fn run_backfill(&mut self, from_block: u64, to_block: Option<u64>) -> anyhow::Result<()> {
+        let mut end = db::get_last_sync_block(&self.db, self.config.watcher.sync_start_block).await?;
+        if let Some(to_block) = to_block {
+            end = cmp::min(end, to_block);
+        }
+        let mut start_block = from_block;
+        while start_block <= end {
+            let end_block = cmp::min(end, start_block + self.sync_step - 1);
+            let logs = self.sync_events(start_block, end_block)
+                .await.map_err(|e| format_err!("sync_events failed,{:?}", e))?;
+            let tx = self.db.acquire_begin().await?;
+            let ret = self.registry.persist(&tx, logs).await;
+            db::finish_transaction(tx, ret).await?;
+            start_block = end_block + 1;
+        }
+        Ok(())
+    }
+
+    /// Syncs claim events from the last synced block up to the confirmed chain head, or to `to_block`
+    /// if it is lower, after rolling back what a reorg orphaned.
+    pub async fn run_sync_events(&mut self, to_block: Option<u64>) ->anyhow::Result<()> {
+        let last_synced_block = db::get_last_sync_block(&self.db,self.config.watcher.sync_start_block).await?;
+        let last_synced_block = self.handle_reorg(last_synced_block).await?;
+        let mut chain_block_number = self.rpc.call("eth_blockNumber", |web3| web3.eth().block_number()).await?.as_u64()
+            .saturating_sub(self.config.watcher.confirmations);
+        if let Some(to_block) = to_block {
+            chain_block_number = cmp::min(chain_block_number, to_block);
+        }
         let mut start_block = last_synced_block + 1;
         let mut end_block;
         loop {
-            end_block = cmp::min(chain_block_number,start_block + sync_step);
+            end_block = cmp::min(chain_block_number,start_block + self.sync_step - 1);
             if start_block > end_block {
                 break;
             }
-            self.sync_claim_events(start_block,end_block)
-                .await.map_err(|e| format_err!("sync_claim_events failed,{:?}",e))?;
+            let end_block_hash = self.block_hash(end_block).await?
+                .ok_or_else(|| format_err!("block {} not found", end_block))?;
+            let logs = self.sync_events(start_block,end_block)
+                .await.map_err(|e| format_err!("sync_events failed,{:?}",e))?;
+            self.save_synced_range(logs, SyncedBlock {
+                block_number: end_block as i64,
+                block_hash: format!("{:?}", end_block_hash),
+            }).await?;
 
             start_block = end_block + 1;
-            db::upsert_last_sync_block(
-                &mut self.db,
-                end_block as i64,
-            ).await?;
 
         }
         Ok(())
     }
 
+    async fn subscribe(&self) -> anyhow::Result<(SubscriptionStream<WebSocket, BlockHeader>, SubscriptionStream<WebSocket, Log>)> {
+        let transport = WebSocket::new(&self.config.watcher.remote_web3_ws_url).await?;
+        let web3 = Web3::new(transport);
+        let heads = web3.eth_subscribe().subscribe_new_heads().await?;
+        let filter = FilterBuilder::default()
+            .address(vec![H160::from_str(&self.config.watcher.contract_address)?])
+            .topics(Some(vec![claimed_abi().signature()]), None, None, None)
+            .build();
+        let claims = web3.eth_subscribe().subscribe_logs(filter).await?;
+        log::info!("subscribed to new heads and claims on {}", self.config.watcher.remote_web3_ws_url);
+        Ok((heads, claims))
+    }
+
+    /// Saves a claim of the subscription as unconfirmed, or forgets it if a reorg removed its log.
+    /// Claims of blocks already synced are skipped, the sync saved them.
+    async fn save_unconfirmed_claim(&self, log: Log) -> anyhow::Result<()> {
+        let removed = log.removed == Some(true);
+        let mut event = ClaimEventRecord::from(ClaimEvent::try_from(log)?);
+        let last_synced_block = db::get_last_sync_block(&self.db, self.config.watcher.sync_start_block).await?;
+        if event.block_number as u64 <= last_synced_block {
+            return Ok(());
+        }
+        event.confirmed = false;
+        let tx = self.db.acquire_begin().await?;
+        let ret = if removed {
+            log::info!("unconfirmed claim of {} in block {} removed", event.address, event.block_number);
+            db::remove_unconfirmed_claim_event(&tx, event.tx_hash, event.log_index).await
+        } else {
+            log::info!("unconfirmed claim of {} in block {}", event.address, event.block_number);
+            db::save_claim_events(&tx, vec![event]).await
+        };
+        db::finish_transaction(tx, ret).await
+    }
+
+    /// Syncs on every tick of the polling interval and, when `REMOTE_WEB3_WS_URL` is set, on every new
+    /// head as well, claims are then indexed `CONFIRMATIONS` blocks after they are mined. The `Claimed`
+    /// logs of that endpoint are saved as they come, unconfirmed until the sync reaches their block.
+    /// Polling keeps filling gaps while the subscriptions are down, they are retried every cycle.
     pub async fn run_watcher_server(mut self) {
-        let mut tx_poll = tokio::time::interval(Duration::from_secs(120));
+        let mut tx_poll = tokio::time::interval(Duration::from_secs(self.config.watcher.poll_interval_secs));
+        let mut heads = None;
+        let mut claims = None;
         loop {
-            tx_poll.tick().await;
-            if self.config.claim_start {
-                if let Err(e) = self.run_sync_events().await {
+            if heads.is_none() && !self.config.watcher.remote_web3_ws_url.is_empty() {
+                match self.subscribe().await {
+                    Ok((h, c)) => {
+                        heads = Some(h);
+                        claims = Some(c);
+                    },
+                    Err(e) => log::warn!("subscriptions failed, polling only,{:?}", e),
+                }
+            }
+            tokio::select! {
+                _ = tx_poll.tick() => {},
+                head = next_item(&mut heads) => {
+                    if let Err(e) = head {
+                        log::warn!("new heads subscription lost, polling only,{:?}", e);
+                        heads = None;
+                        claims = None;
+                        continue;
+                    }
+                },
+                claim = next_item(&mut claims) => {
+                    match claim {
+                        Ok(log) if self.campaign.current().is_claim_started() => {
+                            if let Err(e) = self.save_unconfirmed_claim(log).await {
+                                log::error!("save unconfirmed claim error occurred {:?}", e);
+                            }
+                        },
+                        Ok(_) => {},
+                        Err(e) => {
+                            log::warn!("claims subscription lost, polling only,{:?}", e);
+                            heads = None;
+                            claims = None;
+                        },
+                    }
+                    continue;
+                },
+            }
+            let block_number = self.rpc.call("eth_blockNumber", |web3| web3.eth().block_number()).await.ok().map(|b| b.as_u64());
+            if let Err(e) = self.campaign.apply_schedule(block_number).await {
+                log::error!("apply campaign phase schedule error occurred {:?}", e);
+            }
+            if self.campaign.current().is_claim_started() {
+                if let Err(e) = self.run_sync_events(None).await {
                     log::error!("run_sync_pair_events error occurred {:?}", e);
                 }
             }
@@ -148,8 +294,19 @@ impl ChainWatcher {
         }
     }
 }
-pub async fn run_watcher(config: Config, db: rbatis::RBatis) -> JoinHandle<()> {
+/// Waits for the next item of the subscription, forever if there is none.
+async fn next_item<T: DeserializeOwned>(stream: &mut Option<SubscriptionStream<WebSocket, T>>) -> anyhow::Result<T> {
+    match stream {
+        Some(stream) => match stream.next().await {
+            Some(item) => Ok(item?),
+            None => Err(format_err!("subscription closed")),
+        },
+        None => futures::future::pending().await,
+    }
+}
+
+pub async fn run_watcher(config: Config, db: rbatis::RBatis, campaign: CampaignState) -> anyhow::Result<JoinHandle<()>> {
     log::info!("Starting watcher!");
-    let watcher = ChainWatcher::new(config, db).await.unwrap();
-    tokio::spawn(watcher.clone().run_watcher_server())
+    let watcher = ChainWatcher::new(config, db, campaign).await?;
+    Ok(tokio::spawn(watcher.run_watcher_server()))
 }
\ No newline at end of file